@@ -0,0 +1,200 @@
+use std::cell::RefCell;
+use std::os::raw::{c_int, c_long};
+use std::rc::Rc;
+
+use wx;
+use wx::methods::*;
+
+use crate::fuzzy;
+
+const TITLE: &str = "コマンドパレット";
+
+#[derive(Clone)]
+pub struct PaletteEntry {
+    pub id: c_int,
+    pub label: String,
+    pub shortcut: String,
+    pub enabled: bool,
+}
+impl PaletteEntry {
+    fn search_text(&self) -> String {
+        format!("{} {}", self.label, self.shortcut)
+    }
+}
+
+/// コマンドパレットを表示し、選択されたコマンドの ID を返します。
+/// 無効なコマンドは灰色で表示され、選択できません。
+pub fn choose<W: WindowMethods>(parent: &W, entries: Vec<PaletteEntry>) -> Option<c_int> {
+    let dialog = wx::Dialog::builder(Some(parent))
+        .title(TITLE)
+        .style((wx::DEFAULT_DIALOG_STYLE | wx::RESIZE_BORDER).into())
+        .build();
+    let search = wx::TextCtrl::builder(Some(&dialog))
+        .style(wx::TE_PROCESS_ENTER.into())
+        .build();
+    let list = wx::ListCtrl::builder(Some(&dialog))
+        .style((wx::LC_REPORT | wx::LC_SINGLE_SEL | wx::LC_NO_HEADER).into())
+        .size(wx::Size::new_with_int(480, 320))
+        .build();
+    list.append_column("コマンド", wx::LIST_FORMAT_LEFT, 320);
+    list.append_column("ショートカット", wx::LIST_FORMAT_LEFT, 140);
+
+    let sizer = wx::BoxSizer::new(wx::VERTICAL);
+    sizer.add_window_int(
+        Some(&search),
+        0,
+        wx::EXPAND | wx::ALL,
+        4,
+        wx::Object::none(),
+    );
+    sizer.add_window_int(
+        Some(&list),
+        1,
+        wx::EXPAND | wx::LEFT | wx::RIGHT | wx::BOTTOM,
+        4,
+        wx::Object::none(),
+    );
+    dialog.set_sizer_and_fit(Some(&sizer), true);
+
+    let entries = Rc::new(entries);
+    // 表示中の行に対応する entries のインデックス
+    let visible = Rc::new(RefCell::new(Vec::new()));
+    let chosen = Rc::new(RefCell::new(None));
+    refill(&list, &entries, &mut visible.borrow_mut(), "");
+
+    let list_copy = list.clone();
+    let entries_copy = entries.clone();
+    let visible_copy = visible.clone();
+    search.bind(wx::RustEvent::Text, move |event: &wx::CommandEvent| {
+        refill(
+            &list_copy,
+            &entries_copy,
+            &mut visible_copy.borrow_mut(),
+            &event.get_string(),
+        );
+    });
+    let list_copy = list.clone();
+    search.bind(wx::RustEvent::KeyDown, move |event: &wx::KeyEvent| {
+        let count = list_copy.get_item_count();
+        let current = list_copy.get_next_item(-1, wx::LIST_NEXT_ALL, wx::LIST_STATE_SELECTED);
+        let next = match event.get_key_code() {
+            wx::K_DOWN => current + 1,
+            wx::K_UP => current - 1,
+            _ => {
+                event.skip(true);
+                return;
+            }
+        };
+        if 0 <= next && next < count {
+            select_row(&list_copy, next);
+        }
+    });
+    let accept = {
+        let dialog = dialog.clone();
+        let list = list.clone();
+        let chosen = chosen.clone();
+        move || {
+            let row = list.get_next_item(-1, wx::LIST_NEXT_ALL, wx::LIST_STATE_SELECTED);
+            if row < 0 {
+                return;
+            }
+            let entry = &entries[visible.borrow()[row as usize]];
+            if !entry.enabled {
+                wx::bell();
+                return;
+            }
+            *chosen.borrow_mut() = Some(entry.id);
+            dialog.end_modal(wx::ID_OK);
+        }
+    };
+    let accept_copy = accept.clone();
+    search.bind(wx::RustEvent::TextEnter, move |_: &wx::CommandEvent| {
+        accept_copy();
+    });
+    list.bind(
+        wx::RustEvent::ListItemActivated,
+        move |_: &wx::ListEvent| {
+            accept();
+        },
+    );
+
+    search.set_focus();
+    let result = dialog.show_modal();
+    dialog.destroy();
+    if result != wx::ID_OK {
+        return None;
+    }
+    let id = *chosen.borrow();
+    id
+}
+
+fn refill(list: &wx::ListCtrl, entries: &[PaletteEntry], visible: &mut Vec<usize>, pattern: &str) {
+    let texts: Vec<String> = entries.iter().map(PaletteEntry::search_text).collect();
+    *visible = fuzzy::filter(pattern, &texts);
+
+    list.delete_all_items();
+    let gray = wx::SystemSettings::get_colour(wx::SYS_COLOUR_GRAYTEXT);
+    for (row, &i) in visible.iter().enumerate() {
+        let entry = &entries[i];
+        let row = row as c_long;
+        list.insert_item_long_str(row, &entry.label);
+        list.set_item_long_int_str(row, 1, &entry.shortcut, -1);
+        if !entry.enabled {
+            list.set_item_text_colour(row, &gray);
+        }
+    }
+    if !visible.is_empty() {
+        select_row(list, 0);
+    }
+}
+
+fn select_row(list: &wx::ListCtrl, row: c_long) {
+    list.set_item_state(
+        row,
+        wx::LIST_STATE_SELECTED | wx::LIST_STATE_FOCUSED,
+        wx::LIST_STATE_SELECTED | wx::LIST_STATE_FOCUSED,
+    );
+    list.ensure_visible(row);
+}
+
+/// メニュー項目のラベル `"保存(&S)"` からニーモニックを取り除きます。
+pub fn strip_mnemonic(text: &str) -> String {
+    // 「保存(&S)」のような日本語式のニーモニックは括弧ごと取り除きます。
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' if chars.peek() == Some(&'&') => {
+                for c in chars.by_ref() {
+                    if c == ')' {
+                        break;
+                    }
+                }
+            }
+            '&' => {
+                if chars.peek() == Some(&'&') {
+                    result.push('&');
+                    chars.next();
+                }
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strip_japanese_mnemonic() {
+        assert_eq!(strip_mnemonic("保存(&S)"), "保存");
+        assert_eq!(strip_mnemonic("フォント(&O)…"), "フォント…");
+    }
+
+    #[test]
+    fn strip_western_mnemonic() {
+        assert_eq!(strip_mnemonic("&Print && Preview"), "Print & Preview");
+    }
+}
@@ -1,6 +1,8 @@
 use std::os::raw::c_int;
+use std::rc::Rc;
 
 use wx;
+use wx::methods::*;
 
 pub trait CommandHandler<C> {
     fn handle_command(&self, command: &C);
@@ -19,6 +21,13 @@ pub enum Command {
     FileOpen,
     FileSave,
     FileSaveAs,
+    FileSaveCopyAs,
+    FileRevert,
+    FileReadOnly,
+    FilePageSetup,
+    FilePrintPreview,
+    FilePrint,
+    FileHotExit,
     FileClose,
     // 編集
     // wx::ID_UNDO,
@@ -27,63 +36,361 @@ pub enum Command {
     // wx::ID_PASTE,
     EditDelete,
     EditFind,
+    EditIncrementalFind,
     EditFindNext,
     EditFindPrevious,
     EditReplace,
     EditGo,
     // wx::ID_SELECTALL,
     EditDate,
+    EditDuplicateLine,
+    EditDeleteLine,
+    EditMoveLineUp,
+    EditMoveLineDown,
+    EditJoinLines,
+    EditUpperCase,
+    EditLowerCase,
+    EditTitleCase,
+    EditFullWidthAlphanumeric,
+    EditHalfWidthAlphanumeric,
+    EditFullWidthKatakana,
+    EditHalfWidthKatakana,
+    EditKatakana,
+    EditHiragana,
+    EditSortAscending,
+    EditSortDescending,
+    EditSortIgnoreCase,
+    EditSortNumeric,
+    EditRemoveDuplicateLines,
+    EditRemoveBlankLines,
+    EditReverseLines,
     // 書式
     FormatWordWrap,
+    FormatTabsToSpaces,
+    FormatSpacesToTabs,
+    FormatTabWidth2,
+    FormatTabWidth4,
+    FormatTabWidth8,
+    FormatIndentWithSpaces,
+    FormatTrimTrailingWhitespace,
+    FormatEnsureFinalNewline,
+    FormatRemoveFinalNewline,
+    FormatTrimOnSave,
+    FormatEnsureFinalNewlineOnSave,
+    FormatRemoveFinalNewlineOnSave,
+    FormatTrimFullWidthSpace,
+    FormatLanguageAuto,
+    FormatPlainText,
     FormatFont,
     // 表示
+    ViewZoomIn,
+    ViewZoomOut,
+    ViewZoomReset,
+    ViewLineNumbers,
+    ViewMarkdownPreview,
+    ViewHex,
     ViewStatusBar,
+    ViewStatistics,
+    ViewCommandPalette,
     // ヘルプ
     Help,
     // wx::ID_ABOUT,
 }
-impl Command {
-    pub fn from(v: c_int) -> Option<Self> {
-        use Command::*;
-        for e in [
-            // ファイル
-            FileNew,
-            FileNewWindow,
-            FileOpen,
-            FileSave,
-            FileSaveAs,
-            FileClose,
-            // 編集
-            // wx::ID_UNDO,
-            // wx::ID_CUT,
-            // wx::ID_COPY,
-            // wx::ID_PASTE,
-            EditDelete,
-            EditFind,
-            EditFindNext,
-            EditFindPrevious,
-            EditReplace,
-            EditGo,
-            // wx::ID_SELECTALL,
-            EditDate,
-            // 書式
-            FormatWordWrap,
-            FormatFont,
-            // 表示
-            ViewStatusBar,
-            // ヘルプ
-            Help,
-            // wx::ID_ABOUT,
-        ] {
-            if v == e.into() {
-                return Some(e);
+impl From<Command> for c_int {
+    fn from(w: Command) -> Self {
+        w as c_int
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MenuId {
+    File,
+    Edit,
+    Format,
+    View,
+    Help,
+}
+impl MenuId {
+    pub const ALL: [MenuId; 5] = [
+        MenuId::File,
+        MenuId::Edit,
+        MenuId::Format,
+        MenuId::View,
+        MenuId::Help,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            MenuId::File => "ファイル(&F)",
+            MenuId::Edit => "編集(&E)",
+            MenuId::Format => "書式(&O)",
+            MenuId::View => "表示(&V)",
+            MenuId::Help => "ヘルプ(&H)",
+        }
+    }
+}
+
+type Predicate<T> = Rc<dyn Fn(&T) -> bool>;
+type Handler<T> = Rc<dyn Fn(&T, &wx::CommandEvent)>;
+
+/// コマンドの ID、ラベル、アクセラレータ、メニュー上の位置、
+/// 有効状態とハンドラをまとめた定義です。
+pub struct CommandSpec<T> {
+    id: c_int,
+    label: String,
+    accel: Option<String>,
+    menu: Option<MenuId>,
+    submenu: Option<String>,
+    separator_before: bool,
+    enabled: Option<Predicate<T>>,
+    checked: Option<Predicate<T>>,
+    handler: Option<Handler<T>>,
+}
+impl<T> CommandSpec<T> {
+    pub fn new<I: Into<c_int>>(id: I, label: &str) -> Self {
+        Self {
+            id: id.into(),
+            label: label.to_owned(),
+            accel: None,
+            menu: None,
+            submenu: None,
+            separator_before: false,
+            enabled: None,
+            checked: None,
+            handler: None,
+        }
+    }
+    pub fn accel(mut self, accel: &str) -> Self {
+        self.accel = Some(accel.to_owned());
+        self
+    }
+    pub fn menu(mut self, menu: MenuId) -> Self {
+        self.menu = Some(menu);
+        self
+    }
+    /// `menu()` で指定したメニューの中の、`label` という名前のサブメニューに入れます。
+    pub fn submenu(mut self, label: &str) -> Self {
+        self.submenu = Some(label.to_owned());
+        self
+    }
+    /// メニュー上でこのコマンドの前に区切り線を入れます。
+    pub fn separator(mut self) -> Self {
+        self.separator_before = true;
+        self
+    }
+    pub fn enabled<F: Fn(&T) -> bool + 'static>(mut self, predicate: F) -> Self {
+        self.enabled = Some(Rc::new(predicate));
+        self
+    }
+    /// チェック項目としてメニューに追加し、チェック状態を `predicate` で決めます。
+    pub fn checked<F: Fn(&T) -> bool + 'static>(mut self, predicate: F) -> Self {
+        self.checked = Some(Rc::new(predicate));
+        self
+    }
+    pub fn handler<F: Fn(&T, &wx::CommandEvent) + 'static>(mut self, handler: F) -> Self {
+        self.handler = Some(Rc::new(handler));
+        self
+    }
+
+    pub fn id(&self) -> c_int {
+        self.id
+    }
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    pub fn accel_text(&self) -> Option<&str> {
+        self.accel.as_deref()
+    }
+    pub fn is_enabled(&self, target: &T) -> bool {
+        // ハンドラのないコマンドは未実装として無効にします。
+        self.handler.is_some() && self.enabled.as_ref().map_or(true, |f| f(target))
+    }
+
+    fn menu_label(&self) -> String {
+        match &self.accel {
+            Some(accel) => format!("{}\t{}", self.label, accel),
+            None => self.label.clone(),
+        }
+    }
+}
+
+/// メニュー、コマンドパレット、ディスパッチの元になるコマンドの一覧です。
+pub struct CommandRegistry<T> {
+    specs: Vec<CommandSpec<T>>,
+}
+impl<T> CommandRegistry<T> {
+    pub fn new() -> Self {
+        Self { specs: Vec::new() }
+    }
+
+    /// 実行時に追加するコマンド用の、他と重ならない ID を払い出します。
+    pub fn new_id() -> c_int {
+        wx::Window::new_control_id(1)
+    }
+
+    pub fn register(&mut self, spec: CommandSpec<T>) {
+        if let Some(existing) = self.specs.iter_mut().find(|s| s.id == spec.id) {
+            *existing = spec;
+        } else {
+            self.specs.push(spec);
+        }
+    }
+
+    pub fn find(&self, id: c_int) -> Option<&CommandSpec<T>> {
+        self.specs.iter().find(|spec| spec.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandSpec<T>> {
+        self.specs.iter()
+    }
+
+    /// ハンドラを取り出します。呼び出し中にコマンドを追加できるよう、
+    /// レジストリを借用したまま呼び出さないでください。
+    pub fn handler(&self, id: c_int) -> Option<Handler<T>> {
+        self.find(id).and_then(|spec| spec.handler.clone())
+    }
+
+    pub fn update_ui(&self, target: &T, event: &wx::UpdateUIEvent) -> bool {
+        if let Some(spec) = self.find(event.get_id()) {
+            event.enable(spec.is_enabled(target));
+            if let Some(checked) = &spec.checked {
+                event.check(checked(target));
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn build_menu_bar(&self) -> wx::MenuBar {
+        let menu_bar = wx::MenuBar::new(0);
+        for menu_id in MenuId::ALL {
+            let specs: Vec<_> = self
+                .specs
+                .iter()
+                .filter(|s| s.menu == Some(menu_id))
+                .collect();
+            let mut menu = wx::Menu::new();
+            let mut is_first = true;
+            let mut added_submenus: Vec<&str> = Vec::new();
+            for spec in specs.iter() {
+                let submenu_label = spec.submenu.as_deref();
+                if let Some(label) = submenu_label {
+                    // サブメニューは最初の項目の位置にまとめて置きます。
+                    if added_submenus.contains(&label) {
+                        continue;
+                    }
+                    added_submenus.push(label);
+                }
+                if spec.separator_before && !is_first {
+                    menu = menu.separator();
+                }
+                is_first = false;
+                match submenu_label {
+                    None => menu = append_item(menu, spec),
+                    Some(label) => {
+                        let mut submenu = wx::Menu::new();
+                        let mut is_first = true;
+                        for spec in specs.iter().filter(|s| s.submenu.as_deref() == Some(label)) {
+                            if spec.separator_before && !is_first {
+                                submenu = submenu.separator();
+                            }
+                            is_first = false;
+                            submenu = append_item(submenu, spec);
+                        }
+                        menu.append_sub_menu(Some(&submenu), label, "");
+                    }
+                }
             }
+            menu_bar.append(Some(&menu), menu_id.title());
         }
-        return None;
+        menu_bar
     }
 }
-impl From<Command> for c_int {
-    fn from(w: Command) -> Self {
-        w as c_int
+
+fn append_item<T>(menu: wx::Menu, spec: &CommandSpec<T>) -> wx::Menu {
+    if spec.checked.is_some() {
+        menu.check_item(spec.id, &spec.menu_label())
+    } else {
+        menu.item(spec.id, &spec.menu_label())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::Cell;
+
+    struct Target {
+        count: Cell<i32>,
+        ready: bool,
+    }
+
+    #[test]
+    fn command_without_handler_is_disabled() {
+        let target = Target {
+            count: Cell::new(0),
+            ready: true,
+        };
+        let spec = CommandSpec::<Target>::new(Command::EditGo, "行へ移動(&G)…");
+        assert!(!spec.is_enabled(&target));
+    }
+
+    #[test]
+    fn enable_predicate_is_evaluated() {
+        let mut target = Target {
+            count: Cell::new(0),
+            ready: false,
+        };
+        let spec = CommandSpec::<Target>::new(Command::EditGo, "行へ移動(&G)…")
+            .enabled(|t| t.ready)
+            .handler(|t, _| t.count.set(t.count.get() + 1));
+        assert!(!spec.is_enabled(&target));
+        target.ready = true;
+        assert!(spec.is_enabled(&target));
+    }
+
+    #[test]
+    fn register_replaces_same_id() {
+        // Given: 登録済みのコマンドがあるとき
+        let mut registry = CommandRegistry::<Target>::new();
+        registry.register(CommandSpec::new(Command::FileNew, "新規(&N)").accel("Ctrl-N"));
+        registry.register(CommandSpec::new(Command::FileOpen, "開く(&O)…"));
+        // When: 同じ ID で登録し直すと
+        registry.register(CommandSpec::new(Command::FileNew, "新規作成(&N)"));
+        // Then: 順序を保ったまま置き換わる
+        let labels: Vec<&str> = registry.iter().map(|spec| spec.label()).collect();
+        assert_eq!(labels, vec!["新規作成(&N)", "開く(&O)…"]);
+        assert!(registry
+            .find(Command::FileNew.into())
+            .unwrap()
+            .accel_text()
+            .is_none());
+    }
+
+    #[test]
+    fn command_registered_at_runtime_is_dispatched() {
+        // Given: 組み込みのコマンドを登録したレジストリに
+        let target = Target {
+            count: Cell::new(0),
+            ready: true,
+        };
+        let mut registry = CommandRegistry::<Target>::new();
+        registry.register(CommandSpec::new(Command::FileNew, "新規(&N)").handler(|_, _| ()));
+        // When: 実行時に払い出した ID でコマンドを追加すると
+        let id = CommandRegistry::<Target>::new_id();
+        registry.register(
+            CommandSpec::new(id, "拡張コマンド")
+                .menu(MenuId::Edit)
+                .handler(|t, _| t.count.set(t.count.get() + 1)),
+        );
+        // Then: 組み込みの ID と重ならず、その ID でハンドラを呼び出せる
+        assert_ne!(id, c_int::from(Command::FileNew));
+        assert!(registry.find(id).unwrap().is_enabled(&target));
+        let handler = registry.handler(id).unwrap();
+        // イベントの種類は使わないので wxEVT_NULL (0) にします。
+        handler(&target, &wx::CommandEvent::new(0, id));
+        assert_eq!(target.count.get(), 1);
     }
 }
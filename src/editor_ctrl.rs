@@ -1,15 +1,29 @@
-use std::cell::RefCell;
-use std::os::raw::{c_long, c_void};
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::ops::Range;
+use std::os::raw::{c_int, c_long};
+use std::path::Path;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64};
 
 use wx::methods::*;
 
 use crate::commands::{Command, CommandHandler, EditorCommand};
+use crate::encoding::{self, Decoded, TextFormat};
+use crate::file_loader;
+use crate::journal;
+use crate::lines::{self, LineEdit};
 use crate::observer::Subject;
+use crate::position;
+use crate::search::{Direction, Searcher};
+use crate::syntax::{self, Language};
+use crate::text_transform::{self, Cleanup};
+use crate::text_view::{self, Backend, TextView};
 
 #[derive(Clone)]
 pub enum DocumentEvent {
     TextModified,
+    ZoomChanged,
 }
 
 pub trait Document {
@@ -17,61 +31,603 @@ pub trait Document {
     fn new_file(&self);
     fn path(&self) -> Option<String>;
     fn is_modified(&self) -> bool;
+    /// 保存すると内容の一部が失われるなら真を返します。
+    fn is_lossy(&self) -> bool;
     fn load_from(&self, file_path: &str);
     fn save_to(&self, file_path: &str) -> bool;
+    /// パスや変更フラグを変えずに、内容を別のファイルに書き出します。
+    fn save_copy_to(&self, file_path: &str) -> bool;
+}
+
+pub const ZOOM_DEFAULT: i32 = 100;
+pub const TAB_WIDTH_DEFAULT: usize = 4;
+const ZOOM_MIN: i32 = 10;
+const ZOOM_MAX: i32 = 500;
+const ZOOM_STEP: i32 = 10;
+
+/// ユーザーが選んだフォントを基準に、表示倍率を掛けたフォントを設定します。
+/// タブの位置は文字の幅で決まるので、フォントを変えるたびに設定し直します。
+struct Zoom {
+    view: Rc<dyn TextView>,
+    base_font: wx::Font,
+    percent: Cell<i32>,
+    tab_width: Cell<usize>,
+    // 1 ノッチに満たないホイールの回転。高精度のタッチパッドは少しずつ回転を送ってきます。
+    wheel_rotation: Cell<i32>,
+}
+impl Zoom {
+    fn set(&self, percent: i32) -> bool {
+        let percent = percent.clamp(ZOOM_MIN, ZOOM_MAX);
+        if percent == self.percent.get() {
+            return false;
+        }
+        self.percent.set(percent);
+        let font = wx::Font::new_with_font(&self.base_font);
+        font.set_fractional_point_size(
+            self.base_font.get_fractional_point_size() * percent as f64 / 100.0,
+        );
+        self.view.set_font(&font);
+        self.view.set_tab_width(self.tab_width.get());
+        true
+    }
+
+    fn step(&self, steps: i32) -> bool {
+        self.set(self.percent.get() + steps * ZOOM_STEP)
+    }
+
+    /// ホイールの回転を貯め、1 ノッチ分たまるごとに 1 段階拡大・縮小します。
+    fn wheel(&self, rotation: i32, delta: i32) -> bool {
+        let (notches, rest) = whole_notches(self.wheel_rotation.get() + rotation, delta);
+        self.wheel_rotation.set(rest);
+        notches != 0 && self.step(notches)
+    }
+}
+
+/// 貯めた回転を、ノッチの数と 1 ノッチに満たない残りに分けます。
+fn whole_notches(rotation: i32, delta: i32) -> (i32, i32) {
+    let delta = delta.max(1);
+    (rotation / delta, rotation % delta)
 }
 
 pub struct EditorCtrl {
-    ctrl: wx::TextCtrl,
+    view: Rc<dyn TextView>,
     events: Rc<RefCell<Subject<DocumentEvent>>>,
+    zoom: Rc<Zoom>,
+    read_only: Cell<bool>,
+    format: Cell<TextFormat>,
+    // 読み込み時に元のバイト列を失った
+    lossy: Cell<bool>,
+    // 色を付けた範囲と、そのときの文字数。消すときはこの範囲だけを書き換えます。
+    highlighted: Cell<Option<(Range<c_long>, c_long)>>,
+    // 保存するときの空白の整理方法
+    cleanup: Cell<Cleanup>,
+    // Tab キーでタブの代わりに空白を入れる
+    indent_with_spaces: Cell<bool>,
+    // 色分けに使う言語
+    language: Cell<syntax::Choice>,
     pub file: Rc<RefCell<Option<String>>>,
 }
 impl EditorCtrl {
-    pub fn new<W: WindowMethods>(parent: &W) -> Self {
-        let textbox = wx::TextCtrl::builder(Some(parent))
-            .style(wx::TE_MULTILINE.into())
-            .build();
+    pub fn new<W: WindowMethods>(parent: &W, backend: Backend) -> Self {
+        let view = text_view::create(parent, backend);
         let events = Rc::new(RefCell::new(Subject::new()));
         let weak_events = Rc::downgrade(&events);
-        textbox.bind(wx::RustEvent::Text, move |_: &wx::CommandEvent| {
+        view.bind_text_changed(Box::new(move || {
             if let Some(events) = weak_events.upgrade() {
                 events.borrow().notify_event(DocumentEvent::TextModified);
             }
+        }));
+        let zoom = Rc::new(Zoom {
+            view: view.clone(),
+            base_font: view.font(),
+            percent: Cell::new(ZOOM_DEFAULT),
+            tab_width: Cell::new(TAB_WIDTH_DEFAULT),
+            wheel_rotation: Cell::new(0),
         });
+        view.set_tab_width(TAB_WIDTH_DEFAULT);
+        let zoom_copy = zoom.clone();
+        let weak_events = Rc::downgrade(&events);
+        view.bind_mouse_wheel(Box::new(move |event: &wx::MouseEvent| {
+            // Ctrl + ホイールで拡大・縮小します。
+            if !event.control_down() {
+                zoom_copy.wheel_rotation.set(0);
+                event.skip(true);
+                return;
+            }
+            if zoom_copy.wheel(event.get_wheel_rotation(), event.get_wheel_delta()) {
+                if let Some(events) = weak_events.upgrade() {
+                    events.borrow().notify_event(DocumentEvent::ZoomChanged);
+                }
+            }
+        }));
         Self {
-            ctrl: textbox,
+            view,
             events,
+            zoom,
+            read_only: Cell::new(false),
+            format: Cell::new(TextFormat::default()),
+            lossy: Cell::new(false),
+            highlighted: Cell::new(None),
+            cleanup: Cell::new(Cleanup::default()),
+            indent_with_spaces: Cell::new(false),
+            language: Cell::new(syntax::Choice::Auto),
             file: Rc::new(RefCell::new(None)),
         }
     }
 
+    /// 表示倍率をパーセントで返します。
+    pub fn zoom(&self) -> i32 {
+        self.zoom.percent.get()
+    }
+
+    pub fn set_zoom(&self, percent: i32) {
+        if self.zoom.set(percent) {
+            self.events
+                .borrow()
+                .notify_event(DocumentEvent::ZoomChanged);
+        }
+    }
+
+    pub fn zoom_in(&self) {
+        self.set_zoom(self.zoom() + ZOOM_STEP);
+    }
+
+    pub fn zoom_out(&self) {
+        self.set_zoom(self.zoom() - ZOOM_STEP);
+    }
+
+    pub fn reset_zoom(&self) {
+        self.set_zoom(ZOOM_DEFAULT);
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.zoom.tab_width.get()
+    }
+
+    pub fn set_tab_width(&self, tab_width: usize) {
+        self.zoom.tab_width.set(tab_width.max(1));
+        self.view.set_tab_width(tab_width.max(1));
+    }
+
+    pub fn indent_with_spaces(&self) -> bool {
+        self.indent_with_spaces.get()
+    }
+
+    pub fn set_indent_with_spaces(&self, indent_with_spaces: bool) {
+        self.indent_with_spaces.set(indent_with_spaces);
+    }
+
+    fn selection(&self) -> (c_long, c_long) {
+        self.view.selection()
+    }
+
     fn delete_selection(&self) {
-        let mut from: c_long = 0;
-        let mut to: c_long = 0;
-        self.ctrl.get_selection_long(
-            &mut from as *mut c_long as *mut c_void,
-            &mut to as *mut c_long as *mut c_void,
-        );
-        self.ctrl.remove(from, to);
+        let (from, to) = self.selection();
+        self.view.replace(from, to, "");
     }
 
-    fn select_all(&self) {
-        self.ctrl.select_all();
+    /// 選択範囲を UTF-8 のバイト位置で返します。
+    fn selection_bytes(&self, text: &str) -> Range<usize> {
+        let (from, to) = self.selection();
+        position::to_byte(text, from, self.view.unit())
+            ..position::to_byte(text, to, self.view.unit())
+    }
+
+    fn select_bytes(&self, text: &str, range: Range<usize>) {
+        let from = position::from_byte(text, range.start, self.view.unit());
+        let to = position::from_byte(text, range.end, self.view.unit());
+        self.view.set_selection(from, to);
+        self.view.show_position(from);
+    }
+
+    /// 選択範囲を UTF-8 のバイト位置で返します。
+    pub fn selection_range(&self) -> Range<usize> {
+        self.selection_bytes(&self.text())
+    }
+
+    /// UTF-8 のバイト位置で範囲を選択します。
+    pub fn select_range(&self, range: Range<usize>) {
+        self.select_bytes(&self.text(), range);
+    }
+
+    /// 選択中の文字列を返します。選択していなければ `None` です。
+    pub fn selected_text(&self) -> Option<String> {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        if selection.is_empty() {
+            None
+        } else {
+            Some(text[selection].to_owned())
+        }
+    }
+
+    /// 選択範囲の後ろ (または前) を検索し、見つかったら選択します。
+    pub fn find(&self, searcher: &Searcher, direction: Direction) -> bool {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        let found = match direction {
+            Direction::Forward => searcher.find_next(&text, selection.end),
+            Direction::Backward => searcher.find_previous(&text, selection.start),
+        };
+        if let Some(range) = found {
+            self.select_bytes(&text, range);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 選択範囲が検索文字列に一致していれば置き換えます。
+    pub fn replace(&self, searcher: &Searcher, replacement: &str) -> bool {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        match searcher.replacement_for(&text, selection, replacement) {
+            Some(replaced) => {
+                let (from, to) = self.selection();
+                self.view.replace(from, to, &replaced);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// すべて置換し、置き換えた数を返します。
+    pub fn replace_all(&self, searcher: &Searcher, replacement: &str) -> usize {
+        let text = self.text();
+        let (replaced, count) = searcher.replace_all(&text, replacement);
+        if count > 0 {
+            let (from, _) = self.selection();
+            self.view.replace(0, self.view.last_position(), &replaced);
+            self.view
+                .set_insertion_point(from.min(self.view.last_position()));
+        }
+        count
+    }
+
+    /// `ranges` (位置の順に並んだもの) の背景に色を付けます。
+    pub fn highlight(&self, ranges: &[Range<usize>]) {
+        self.clear_highlight();
+        let text = self.text();
+        let unit = self.view.unit();
+        // 先頭から変換し直さないよう、前の範囲からの差分で位置を求めます。
+        let mut last = 0;
+        let mut pos = 0;
+        let mut span = None;
+        for range in ranges {
+            pos += position::from_byte(&text[last..], range.start - last, unit);
+            let end = pos + position::from_byte(&text[range.start..], range.len(), unit);
+            self.view.highlight(pos, end);
+            span = Some(span.map_or(pos, |span: Range<c_long>| span.start)..end);
+            pos = end;
+            last = range.end;
+        }
+        self.highlighted
+            .set(span.map(|span| (span, self.view.last_position())));
+    }
+
+    pub fn clear_highlight(&self) {
+        if let Some((span, last_position)) = self.highlighted.take() {
+            // 色を付けた後に編集していれば、その分だけ広げて消します。
+            let delta = self.view.last_position() - last_position;
+            self.view
+                .clear_highlight((span.start + delta.min(0)).max(0), span.end + delta.max(0));
+        }
+    }
+
+    /// 色を付けると元に戻す操作の履歴に入るなら、入力のたびに付け直さないでください。
+    pub fn highlight_is_undoable(&self) -> bool {
+        self.view.highlight_is_undoable()
+    }
+
+    /// 編集コントロールをサイザーに追加します。
+    pub fn add_to_sizer(&self, sizer: &wx::BoxSizer, proportion: c_int, flag: c_int) {
+        self.view.add_to_sizer(sizer, proportion, flag);
+    }
+
+    /// キー入力を `on_key_down()` に渡すよう、呼び出し側から登録します。
+    pub fn bind_key_down(&self, handler: Box<dyn Fn(&wx::KeyEvent)>) {
+        self.view.bind_key_down(handler);
+    }
+
+    pub fn set_focus(&self) {
+        self.view.set_focus();
+    }
+
+    /// 読み込んだテキストを表示し、ファイルのパスを設定します。
+    pub fn set_contents(&self, path: Option<&str>, decoded: &Decoded) {
+        // 別のファイルを開いたら、言語はファイル名から決め直します。
+        if path.map(ToOwned::to_owned) != self.path() {
+            self.language.set(syntax::Choice::Auto);
+        }
+        self.view.set_text(&decoded.text);
+        self.format.set(decoded.format);
+        self.lossy.set(decoded.lossy);
+        self.set_path(path);
+        self.events
+            .borrow()
+            .notify_event(DocumentEvent::TextModified);
+    }
+
+    pub fn text_format(&self) -> TextFormat {
+        self.format.get()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.get()
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.set(read_only);
+        self.view.set_editable(!read_only);
     }
 
     fn set_path(&self, path: Option<&str>) {
         *self.file.borrow_mut() = path.map(ToOwned::to_owned);
         self.reset_modified();
+        // 別名で保存すると拡張子が変わるかもしれません。
+        self.view.set_language(self.language());
+    }
+
+    pub fn supports_syntax(&self) -> bool {
+        self.view.supports_syntax()
+    }
+
+    pub fn language_choice(&self) -> syntax::Choice {
+        self.language.get()
+    }
+
+    /// 色分けに使っている言語を返します。
+    pub fn language(&self) -> Option<&'static Language> {
+        self.language.get().resolve(self.path().as_deref())
+    }
+
+    pub fn set_language_choice(&self, choice: syntax::Choice) {
+        self.language.set(choice);
+        self.view.set_language(self.language());
     }
 
     fn reset_modified(&self) {
-        self.ctrl.set_modified(false);
+        self.view.set_modified(false);
+    }
+
+    pub fn text(&self) -> String {
+        self.view.text()
+    }
+
+    /// 表示倍率を掛ける前の、ユーザーが選んだフォントを返します。
+    pub fn font(&self) -> wx::Font {
+        wx::Font::new_with_font(&self.zoom.base_font)
+    }
+
+    pub fn word_wrap(&self) -> bool {
+        self.view.word_wrap()
+    }
+
+    pub fn set_word_wrap(&self, word_wrap: bool) {
+        self.view.set_word_wrap(word_wrap);
+    }
+
+    pub fn line_numbers(&self) -> bool {
+        self.view.line_numbers()
+    }
+
+    pub fn set_line_numbers(&self, visible: bool) {
+        self.view.set_line_numbers(visible);
+    }
+
+    /// キャレットの位置を UTF-8 のバイト位置で返します。
+    pub fn caret(&self) -> usize {
+        position::to_byte(&self.text(), self.view.insertion_point(), self.view.unit())
+    }
+
+    pub fn set_caret(&self, caret: usize) {
+        let text = self.text();
+        let caret = position::from_byte(&text, caret, self.view.unit());
+        self.view.set_insertion_point(caret);
+    }
+
+    /// 一番上に表示している行を返します。
+    pub fn first_visible_line(&self) -> i32 {
+        self.view.first_visible_line()
     }
 
-    pub fn on_update_ui(&self, event: &wx::UpdateUIEvent) {
-        if event.get_id() == wx::ID_UNDO {
-            event.enable(self.ctrl.can_undo());
+    pub fn scroll_to_line(&self, line: i32) {
+        self.view.scroll_to_line(line);
+    }
+
+    pub fn cleanup(&self) -> Cleanup {
+        self.cleanup.get()
+    }
+
+    pub fn set_cleanup(&self, cleanup: Cleanup) {
+        self.cleanup.set(cleanup);
+    }
+
+    /// 行末の空白や末尾の改行を整理します。
+    /// キャレットは同じ行と桁に残し、まとめて 1 回で元に戻せるよう一度に置き換えます。
+    pub fn clean_up_whitespace(&self, cleanup: Cleanup) {
+        let text = self.text();
+        let cleaned = cleanup.apply(&text);
+        if cleaned == text {
+            return;
         }
+        let caret = text_transform::map_caret(&text, self.caret(), &cleaned);
+        self.view.replace(0, self.view.last_position(), &cleaned);
+        self.set_caret(caret);
+    }
+
+    fn write_text(&self, file_path: &str, text: &str) -> bool {
+        let (bytes, _) = encoding::encode(text, self.format.get());
+        fs::write(file_path, bytes).is_ok()
+    }
+
+    pub fn has_selection(&self) -> bool {
+        let (from, to) = self.selection();
+        from != to
+    }
+
+    /// 選択中の文字列を `transform` で書き換え、書き換えた文字列を選択し直します。
+    /// 1 回で元に戻せるよう一度に置き換えます。
+    pub fn transform_selection<F: Fn(&str) -> String>(&self, transform: F) {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        if selection.is_empty() {
+            return;
+        }
+        let transformed = transform(&text[selection.clone()]);
+        if transformed != text[selection.clone()] {
+            let (from, to) = self.selection();
+            self.view.replace(from, to, &transformed);
+        }
+        self.select_range(selection.start..selection.start + transformed.len());
+    }
+
+    /// 選択範囲を含む行、選択していなければ文書全体を `transform` で書き換えます。
+    /// 1 回で元に戻せるよう一度に置き換え、書き換えた行を選択し直します。
+    pub fn transform_lines<F: Fn(&str) -> String>(&self, transform: F) {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        if selection.is_empty() {
+            let transformed = transform(&text);
+            if transformed != text {
+                let caret = text_transform::map_caret(&text, selection.start, &transformed);
+                self.view
+                    .replace(0, self.view.last_position(), &transformed);
+                self.set_caret(caret);
+            }
+            return;
+        }
+        let lines = text_transform::line_range(&text, selection);
+        let transformed = transform(&text[lines.clone()]);
+        if transformed != text[lines.clone()] {
+            let from = position::from_byte(&text, lines.start, self.view.unit());
+            let to = position::from_byte(&text, lines.end, self.view.unit());
+            self.view.replace(from, to, &transformed);
+        }
+        self.select_range(lines.start..lines.start + transformed.len());
+    }
+
+    /// 行の複製やインデントを、1 回で元に戻せるよう一度に置き換えて反映します。
+    fn apply_line_edit(&self, edit: LineEdit) {
+        let text = self.text();
+        let from = position::from_byte(&text, edit.range.start, self.view.unit());
+        let to = position::from_byte(&text, edit.range.end, self.view.unit());
+        self.view.replace(from, to, &edit.replacement);
+        self.select_range(edit.selection);
+    }
+
+    /// キャレットのある行、または選択範囲の行に `edit` を適用します。
+    fn edit_lines<F: Fn(&str, Range<usize>) -> Option<LineEdit>>(&self, edit: F) {
+        if self.is_read_only() {
+            return;
+        }
+        let text = self.text();
+        if let Some(edit) = edit(&text, self.selection_bytes(&text)) {
+            self.apply_line_edit(edit);
+        }
+    }
+
+    /// Tab と Shift-Tab でインデントを増減し、Enter で前の行のインデントを引き継ぎます。
+    /// それ以外のキーはコントロールに任せます。
+    pub fn on_key_down(&self, event: &wx::KeyEvent) {
+        let handled = !self.is_read_only()
+            && !event.control_down()
+            && !event.alt_down()
+            && match event.get_key_code() {
+                wx::K_TAB => self.indent_by_key(event.shift_down()),
+                wx::K_RETURN | wx::K_NUMPAD_ENTER if !event.shift_down() => {
+                    self.new_line_with_indent();
+                    true
+                }
+                _ => false,
+            };
+        if !handled {
+            event.skip(true);
+        }
+    }
+
+    /// 複数行を選択していれば行ごとにインデントを増やし、Shift-Tab ならいつも減らします。
+    /// それ以外で空白を使う設定なら、次のタブ位置まで空白を入れます。
+    fn indent_by_key(&self, outdent: bool) -> bool {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        let tab_width = self.tab_width();
+        if outdent {
+            if let Some(edit) = lines::outdent(&text, selection, tab_width) {
+                self.apply_line_edit(edit);
+            }
+        } else if text[selection.clone()].contains('\n') {
+            let unit = if self.indent_with_spaces() {
+                " ".repeat(tab_width)
+            } else {
+                "\t".to_owned()
+            };
+            self.apply_line_edit(lines::indent(&text, selection, &unit));
+        } else if self.indent_with_spaces() {
+            let spaces = lines::soft_tab(&text, selection.start, tab_width);
+            let caret = selection.start + spaces.len();
+            self.apply_line_edit(LineEdit {
+                range: selection,
+                replacement: spaces,
+                selection: caret..caret,
+            });
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn new_line_with_indent(&self) {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        self.apply_line_edit(lines::new_line(&text, selection));
+    }
+
+    /// キャレットの位置に現在の日時を挿入します。
+    pub fn insert_date(&self) {
+        let text = self.text();
+        let selection = self.selection_bytes(&text);
+        let replacement = journal::now();
+        let caret = selection.start + replacement.len();
+        self.apply_line_edit(LineEdit {
+            range: selection,
+            replacement,
+            selection: caret..caret,
+        });
+    }
+
+    /// 先頭の行が「.LOG」なら、末尾に日時を追記してキャレットをそこに置きます。
+    pub fn append_log_entry(&self) {
+        if self.is_read_only() {
+            return;
+        }
+        let text = self.text();
+        if !journal::is_log(&text) {
+            return;
+        }
+        let end = self.view.last_position();
+        self.view
+            .replace(end, end, &journal::entry(&text, &journal::now()));
+        let end = self.view.last_position();
+        self.view.set_insertion_point(end);
+        self.view.show_position(end);
+    }
+
+    /// 終了時に退避した、保存していない無題の内容を復元します。
+    pub fn restore_unsaved(&self, text: &str) {
+        self.view.set_text(text);
+        self.format.set(TextFormat::default());
+        self.lossy.set(false);
+        self.set_path(None);
+        // 保存していない内容なので、変更ありにします。
+        self.view.set_modified(true);
+        self.events
+            .borrow()
+            .notify_event(DocumentEvent::TextModified);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.view.can_undo()
     }
 }
 impl<'a> CommandHandler<EditorCommand<'a, wx::CommandEvent>> for EditorCtrl {
@@ -81,16 +637,22 @@ impl<'a> CommandHandler<EditorCommand<'a, wx::CommandEvent>> for EditorCtrl {
                 Command::EditDelete => {
                     self.delete_selection();
                 }
+                Command::EditDate => {
+                    self.insert_date();
+                }
+                Command::EditDuplicateLine => {
+                    self.edit_lines(|text, selection| Some(lines::duplicate(text, selection)));
+                }
+                Command::EditDeleteLine => {
+                    self.edit_lines(|text, selection| Some(lines::delete(text, selection)));
+                }
+                Command::EditMoveLineUp => self.edit_lines(lines::move_up),
+                Command::EditMoveLineDown => self.edit_lines(lines::move_down),
+                Command::EditJoinLines => self.edit_lines(lines::join),
                 _ => (),
             },
             EditorCommand::StandardEvents(event) => {
-                if event.get_id() == wx::ID_SELECTALL {
-                    // GTK+ では wx::TextCtrl が wx::ID_SELECTALL を処理しないため、
-                    // 自前で呼び出します。
-                    self.select_all();
-                    return;
-                }
-                self.ctrl.process_event(*event);
+                self.view.process_standard_event(event);
             }
         }
     }
@@ -100,22 +662,66 @@ impl Document for EditorCtrl {
         self.events.clone()
     }
     fn new_file(&self) {
-        self.ctrl.clear();
+        self.view.set_text("");
+        self.format.set(TextFormat::default());
+        self.lossy.set(false);
+        self.language.set(syntax::Choice::Auto);
         self.set_path(None);
+        self.set_read_only(false);
+        self.events
+            .borrow()
+            .notify_event(DocumentEvent::TextModified);
     }
     fn path(&self) -> Option<String> {
         self.file.borrow().clone()
     }
     fn is_modified(&self) -> bool {
-        self.ctrl.is_modified()
+        self.view.is_modified()
+    }
+    fn is_lossy(&self) -> bool {
+        self.lossy.get() || encoding::encode(&self.text(), self.format.get()).1
     }
     fn load_from(&self, file_path: &str) {
-        self.ctrl.load_file(file_path, wx::TEXT_TYPE_ANY);
-        self.set_path(Some(&file_path));
+        let progress = AtomicU64::new(0);
+        let cancel = AtomicBool::new(false);
+        if let Ok(bytes) = file_loader::read(Path::new(file_path), None, &progress, &cancel) {
+            let decoded = encoding::decode(&bytes);
+            // バイナリファイルは読み取り専用で開きます。
+            let read_only = decoded.is_none();
+            let decoded = decoded.unwrap_or_else(|| encoding::decode_lossy(&bytes));
+            self.set_contents(Some(file_path), &decoded);
+            self.set_read_only(read_only);
+        }
     }
     fn save_to(&self, file_path: &str) -> bool {
-        let result = self.ctrl.save_file(file_path, wx::TEXT_TYPE_ANY);
+        // 書き込めなかったときに編集中のテキストが変わらないよう、
+        // 整理したテキストを書き込んでから、編集中のテキストも整理します。
+        let cleanup = self.cleanup.get();
+        if !self.write_text(file_path, &cleanup.apply(&self.text())) {
+            return false;
+        }
+        self.clean_up_whitespace(cleanup);
+        // 保存した内容が元のバイト列になります。
+        self.lossy.set(false);
         self.set_path(Some(file_path));
-        result
+        true
+    }
+    fn save_copy_to(&self, file_path: &str) -> bool {
+        self.write_text(file_path, &self.text())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wheel_rotation_is_counted_in_whole_notches() {
+        // 1 ノッチ (120) に満たない回転は残しておく
+        assert_eq!(whole_notches(40, 120), (0, 40));
+        assert_eq!(whole_notches(40 + 40 + 40, 120), (1, 0));
+        assert_eq!(whole_notches(-300, 120), (-2, -60));
+        // 1 回で数ノッチ分回ることもある
+        assert_eq!(whole_notches(360, 120), (3, 0));
     }
 }
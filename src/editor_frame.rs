@@ -1,12 +1,38 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::ops::Range;
 use std::os::raw::c_int;
-use std::rc::Rc;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use wx;
 use wx::methods::*;
 
-use crate::commands::{Command, CommandHandler, EditorCommand};
-use crate::editor_ctrl::{Document, DocumentEvent, EditorCtrl};
+use crate::command_palette::{self, PaletteEntry};
+use crate::commands::{
+    Command, CommandHandler, CommandRegistry, CommandSpec, EditorCommand, MenuId,
+};
+use crate::editor_ctrl::{self, Document, DocumentEvent, EditorCtrl};
+use crate::encoding::{self, Decoded, TextFormat};
+use crate::file_loader::{self, LoadError};
+use crate::find_bar::{FindBar, FindBarEvent};
+use crate::find_dialog::{FindAction, FindDialog};
+use crate::hex;
+use crate::lines::{self, SortOptions};
+use crate::markdown_preview::MarkdownPreview;
 use crate::observer::Observer;
+use crate::printing;
+use crate::search::{self, Direction, FindQuery, InvalidPattern, SearchOptions, Searcher};
+use crate::session::{self, Session, WindowState};
+use crate::settings::{self, Settings};
+use crate::statistics::{self, Statistics};
+use crate::syntax;
+use crate::text_transform::{self, Cleanup, FinalNewline};
 use crate::unsaved_changes;
 
 const APP_NAME: &str = "カニツメエディタ";
@@ -14,9 +40,62 @@ const UNTITLED: &str = "無題";
 
 const CW_USEDEFAULT: c_int = c_int::MIN;
 
+const STATUS_FIELDS: c_int = 3;
+const STATUS_MESSAGE: c_int = 0;
+const STATUS_INDENT: c_int = 1;
+const STATUS_ZOOM: c_int = 2;
+
+// クイック検索で色を付ける一致の数。多すぎると入力のたびの色付けが重くなります。
+const MAX_HIGHLIGHTS: usize = 1000;
+
+// これより小さいファイルは進捗を表示せずに読み込みます。
+const PROGRESS_MIN_SIZE: u64 = 4 * file_loader::MB;
+
+thread_local! {
+    // 開いているウィンドウ。終了時に、その時点で開いているウィンドウをセッションに保存します。
+    static OPEN_FRAMES: RefCell<Vec<Weak<EditorFrame>>> = RefCell::new(Vec::new());
+}
+
+fn open_frames() -> Vec<Rc<EditorFrame>> {
+    OPEN_FRAMES.with(|frames| frames.borrow().iter().filter_map(Weak::upgrade).collect())
+}
+
+/// 開いているすべてのウィンドウの状態を、次の起動のために保存します。
+fn save_session() {
+    let windows = open_frames()
+        .iter()
+        .map(|frame| frame.window_state())
+        .collect();
+    // 保存できなくても終了は続けます。
+    _ = Session { windows }.save();
+}
+
 pub struct EditorFrame {
     base: wx::Frame,
     editor: EditorCtrl,
+    commands: RefCell<CommandRegistry<EditorFrame>>,
+    page_setup: RefCell<wx::PageSetupDialogData>,
+    header: RefCell<String>,
+    footer: RefCell<String>,
+    find_query: RefCell<FindQuery>,
+    find_dialog: RefCell<Option<Rc<FindDialog>>>,
+    // 「選択範囲内」で検索する範囲。ダイアログを開いたときの選択範囲です。
+    find_scope: RefCell<Option<Range<usize>>>,
+    find_bar: Rc<FindBar>,
+    // クイック検索を始めた位置。入力するたびにここから検索し直します。
+    find_bar_anchor: Cell<usize>,
+    preview: Rc<MarkdownPreview>,
+    // Markdown の文書ならプレビューを表示する
+    preview_enabled: Cell<bool>,
+    hot_exit: Cell<bool>,
+    // 行の並べ替えで、大文字と小文字を区別しないか、数として比べるか
+    sort_options: Cell<SortOptions>,
+    // 大きなファイルの先頭だけを読み込んだ。保存すると残りが失われます。
+    partial: Cell<bool>,
+    // 前回の終了時に退避した無題の内容
+    backup: RefCell<Option<String>>,
+    // ダイアログのコールバックから自身を参照するための弱参照
+    this: Weak<EditorFrame>,
 }
 impl EditorFrame {
     pub fn new() -> Rc<Self> {
@@ -31,30 +110,95 @@ impl EditorFrame {
         let frame = wx::Frame::builder(wx::Window::none())
             .size(default_size)
             .build();
-        let editor = EditorCtrl::new(&frame);
-        let frame = Rc::new(EditorFrame {
-            base: frame,
-            editor,
+        let config = Settings::load();
+        let editor = EditorCtrl::new(
+            &frame,
+            config.get(settings::EDITOR_BACKEND).unwrap_or_default(),
+        );
+        let frame = Rc::new_cyclic(|this: &Weak<EditorFrame>| {
+            let this_copy = this.clone();
+            let find_bar = FindBar::new(&frame, move |event| {
+                if let Some(frame) = this_copy.upgrade() {
+                    frame.on_find_bar(event);
+                }
+            });
+            let this_copy = this.clone();
+            let preview = MarkdownPreview::new(&frame, move || {
+                if let Some(frame) = this_copy.upgrade() {
+                    frame.refresh_preview();
+                }
+            });
+            // エディタとプレビューを左右に並べます。
+            let body = wx::BoxSizer::new(wx::HORIZONTAL);
+            editor.add_to_sizer(&body, 1, wx::EXPAND);
+            body.add_window_int(
+                Some(preview.window()),
+                1,
+                wx::EXPAND | wx::LEFT,
+                2,
+                wx::Object::none(),
+            );
+            let sizer = wx::BoxSizer::new(wx::VERTICAL);
+            sizer.add_sizer_int(Some(&body), 1, wx::EXPAND, 0, wx::Object::none());
+            sizer.add_window_int(
+                Some(find_bar.window()),
+                0,
+                wx::EXPAND,
+                0,
+                wx::Object::none(),
+            );
+            frame.set_sizer(Some(&sizer), true);
+            EditorFrame {
+                base: frame,
+                editor,
+                commands: RefCell::new(builtin_commands()),
+                page_setup: RefCell::new(wx::PageSetupDialogData::new()),
+                header: RefCell::new(printing::DEFAULT_HEADER.to_owned()),
+                footer: RefCell::new(printing::DEFAULT_FOOTER.to_owned()),
+                find_query: RefCell::new(FindQuery::default()),
+                find_dialog: RefCell::new(None),
+                find_scope: RefCell::new(None),
+                find_bar,
+                find_bar_anchor: Cell::new(0),
+                preview,
+                preview_enabled: Cell::new(false),
+                hot_exit: Cell::new(false),
+                sort_options: Cell::new(SortOptions::default()),
+                partial: Cell::new(false),
+                backup: RefCell::new(None),
+                this: this.clone(),
+            }
         });
+        OPEN_FRAMES.with(|frames| frames.borrow_mut().push(Rc::downgrade(&frame)));
         let frame_copy = frame.clone();
         frame.editor.events().borrow_mut().add_observer(frame_copy);
         let frame_copy = frame.clone();
         frame
             .base
             .bind(wx::RustEvent::Menu, move |event: &wx::CommandEvent| {
-                let command = Command::from(event.get_id())
-                    .map(|command| EditorCommand::Command(command))
-                    .unwrap_or(EditorCommand::StandardEvents(event));
-                frame_copy.handle_command(&command);
+                frame_copy.handle_command(event);
             });
         let frame_copy = frame.clone();
         frame
             .base
             .bind(wx::RustEvent::UpdateUI, move |event: &wx::UpdateUIEvent| {
-                let command = Command::from(event.get_id())
-                    .map(|command| EditorCommand::Command(command))
-                    .unwrap_or(EditorCommand::StandardEvents(event));
-                frame_copy.on_update_ui(&event, &command);
+                frame_copy.on_update_ui(&event);
+            });
+        let frame_copy = frame.clone();
+        frame
+            .editor
+            .bind_key_down(Box::new(move |event: &wx::KeyEvent| {
+                frame_copy.editor.on_key_down(event);
+            }));
+        let frame_copy = frame.clone();
+        frame
+            .base
+            .bind(wx::RustEvent::Idle, move |event: &wx::IdleEvent| {
+                // スクロールは通知されないので、手が空いたときにプレビューの位置を合わせます。
+                frame_copy
+                    .preview
+                    .sync_scroll(frame_copy.editor.first_visible_line());
+                event.skip(true);
             });
         let frame_copy = frame.clone();
         frame
@@ -62,7 +206,36 @@ impl EditorFrame {
             .bind(wx::RustEvent::CloseWindow, move |event: &wx::CloseEvent| {
                 frame_copy.on_close(&event);
             });
-        frame.build_menu();
+        // 言語の一覧は実行時に追加するコマンドとして登録し、メニューを作ります。
+        frame.register_commands(language_commands());
+        frame.build_status_bar();
+        if let Some(zoom) = config.get(settings::ZOOM) {
+            frame.editor.set_zoom(zoom);
+        }
+        frame
+            .editor
+            .set_line_numbers(config.get(settings::LINE_NUMBERS).unwrap_or(false));
+        frame
+            .preview_enabled
+            .set(config.get(settings::MARKDOWN_PREVIEW).unwrap_or(false));
+        frame
+            .hot_exit
+            .set(config.get(settings::HOT_EXIT).unwrap_or(false));
+        frame.editor.set_cleanup(default_cleanup(&config));
+        frame.sort_options.set(SortOptions {
+            ignore_case: config.get(settings::SORT_IGNORE_CASE).unwrap_or(false),
+            numeric: config.get(settings::SORT_NUMERIC).unwrap_or(false),
+            ..Default::default()
+        });
+        frame.editor.set_tab_width(
+            config
+                .get(settings::TAB_WIDTH)
+                .unwrap_or(editor_ctrl::TAB_WIDTH_DEFAULT),
+        );
+        frame
+            .editor
+            .set_indent_with_spaces(config.get(settings::INDENT_WITH_SPACES).unwrap_or(false));
+        frame.update_indent_status();
         frame.update_title();
 
         frame
@@ -73,51 +246,52 @@ impl EditorFrame {
     }
 
     fn build_menu(&self) {
-        let menu_bar = wx::MenuBar::new(0);
-
-        let file_menu = wx::Menu::new()
-            .item(Command::FileNew, "新規(&N)\tCtrl-N")
-            .item(Command::FileNewWindow, "新しいウィンドウ(&W)\tCtrl-Shift-N")
-            .item(Command::FileOpen, "開く(&W)…\tCtrl-O")
-            .item(Command::FileSave, "保存(&S)\tCtrl-S")
-            .item(Command::FileSaveAs, "名前を付けて保存(&A)…\tCtrl-Shift-S")
-            .separator()
-            .item(Command::FileClose, "終了(&X)\tCtrl-W");
-        menu_bar.append(Some(&file_menu), "ファイル(&F)");
+        let menu_bar = self.commands.borrow().build_menu_bar();
+        self.base.set_menu_bar(Some(&menu_bar));
+    }
 
-        let edit_menu = wx::Menu::new()
-            .item(wx::ID_UNDO, "元に戻す(&U)\tCtrl-Z")
-            .separator()
-            .item(wx::ID_CUT, "切り取り(&T)\tCtrl-X")
-            .item(wx::ID_COPY, "コピー(&C)\tCtrl-C")
-            .item(wx::ID_PASTE, "貼り付け(&P)\tCtrl-V")
-            .item(Command::EditDelete, "削除(&L)\tDel")
-            .separator()
-            .item(Command::EditFind, "検索(&F)…\tCtrl-F")
-            .item(Command::EditFindNext, "次を検索(&N)\tF3")
-            .item(Command::EditFindPrevious, "前を検索(&V)\tShift-F3")
-            .item(Command::EditReplace, "置換(&R)…\tCtrl-H")
-            .item(Command::EditGo, "行へ移動(&G)…\tCtrl-G")
-            .separator()
-            .item(wx::ID_SELECTALL, "すべて選択(&A)\tCtrl-A")
-            .item(Command::EditDate, "日付と時刻(&D)\tF5");
-        menu_bar.append(Some(&edit_menu), "編集(&E)");
+    fn build_status_bar(&self) {
+        let status_bar =
+            self.base
+                .create_status_bar(STATUS_FIELDS, wx::STB_DEFAULT_STYLE, wx::ID_ANY, "");
+        status_bar.set_status_widths(STATUS_FIELDS, [-1, 120, 60].as_ptr());
+        self.update_status_bar();
+    }
 
-        let format_menu = wx::Menu::new()
-            .check_item(Command::FormatWordWrap, "右端で折り返す(&W)")
-            .item(Command::FormatFont, "フォント(&O)…");
-        menu_bar.append(Some(&format_menu), "書式(&O)");
+    fn update_status_bar(&self) {
+        self.base
+            .set_status_text(&format!("{}%", self.editor.zoom()), STATUS_ZOOM);
+    }
 
-        let view_menu = wx::Menu::new().check_item(Command::ViewStatusBar, "ステータスバー(&S)");
-        menu_bar.append(Some(&view_menu), "表示(&V)");
+    /// 文書のインデントの種類をステータスバーに表示します。
+    fn update_indent_status(&self) {
+        let indentation = text_transform::detect_indentation(&self.editor.text());
+        self.base
+            .set_status_text(&format!("インデント: {}", indentation), STATUS_INDENT);
+    }
 
-        let help_menu = wx::Menu::new()
-            .item(Command::Help, "ヘルプの表示(&H)")
-            .separator()
-            .item(wx::ID_ABOUT, "バージョン情報(&A)");
-        menu_bar.append(Some(&help_menu), "ヘルプ(&H)");
+    fn is_status_bar_shown(&self) -> bool {
+        self.base
+            .get_status_bar()
+            .map_or(false, |status_bar| status_bar.is_shown())
+    }
 
-        self.base.set_menu_bar(Some(&menu_bar));
+    fn toggle_status_bar(&self) {
+        if let Some(status_bar) = self.base.get_status_bar() {
+            status_bar.show(!status_bar.is_shown());
+            self.base.send_size_event(0);
+        }
+    }
+
+    /// 実行時にコマンドを追加し、メニューを作り直します。
+    pub fn register_commands<I: IntoIterator<Item = CommandSpec<EditorFrame>>>(&self, specs: I) {
+        {
+            let mut commands = self.commands.borrow_mut();
+            for spec in specs {
+                commands.register(spec);
+            }
+        }
+        self.build_menu();
     }
 
     pub fn new_file(&self) {
@@ -126,6 +300,10 @@ impl EditorFrame {
                 return;
             }
             editor.new_file();
+            editor.set_cleanup(default_cleanup(&Settings::load()));
+            self.partial.set(false);
+            self.update_indent_status();
+            self.update_title();
         });
     }
 
@@ -134,16 +312,227 @@ impl EditorFrame {
             if !saved {
                 return;
             }
-            if let Some(path) = path {
-                editor.load_from(path);
+            let path = match path {
+                Some(path) => path.to_owned(),
+                None => {
+                    let file_dialog = wx::FileDialog::builder(Some(&self.base)).build();
+                    if wx::ID_OK != file_dialog.show_modal() {
+                        return;
+                    }
+                    file_dialog.get_path()
+                }
+            };
+            // 日時を追記するのは、ユーザーが開いたときだけです。
+            // 元に戻すときやセッションの復元では追記しません。
+            if self.load_file(&path) {
+                self.editor.append_log_entry();
+            }
+        });
+    }
+
+    /// ファイルを読み込みます。読み込まなかったら偽を返します。
+    fn load_file(&self, path: &str) -> bool {
+        let size = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                self.show_error(&format!("{} を開けません。\n{}", path, e));
+                return false;
+            }
+        };
+        let settings = Settings::load();
+        let threshold = settings
+            .get(settings::LARGE_FILE_THRESHOLD_MB)
+            .unwrap_or(file_loader::DEFAULT_LARGE_FILE_THRESHOLD_MB);
+        let preview = settings
+            .get(settings::PREVIEW_MB)
+            .unwrap_or(file_loader::DEFAULT_PREVIEW_MB);
+        let mut limit = None;
+        if size > threshold * file_loader::MB {
+            let answer = wx::message_box(
+                &format!(
+                    "このファイルは {} MB あります。読み込みに時間がかかることがあります。\n\n\
+                     すべて読み込むには「はい」を、\
+                     先頭の {} MB だけを読み取り専用で開くには「いいえ」を選んでください。",
+                    size / file_loader::MB,
+                    preview
+                ),
+                APP_NAME,
+                wx::YES_NO | (wx::CANCEL | wx::ICON_WARNING | wx::CENTRE) as c_int,
+                Some(&self.base),
+            );
+            match answer {
+                wx::YES => (),
+                wx::NO => limit = Some(preview * file_loader::MB),
+                _ => return false,
+            }
+        }
+        let bytes = match self.read_with_progress(path, size, limit) {
+            Ok(bytes) => bytes,
+            Err(LoadError::Cancelled) => return false,
+            Err(LoadError::Io(e)) => {
+                self.show_error(&format!("{} を読み込めません。\n{}", path, e));
+                return false;
+            }
+        };
+        let decoded = if limit.is_some() {
+            // 途中で切った文字は捨てます。
+            encoding::decode_prefix(&bytes)
+        } else {
+            encoding::decode(&bytes)
+        };
+        let unwritable = !file_loader::is_writable(Path::new(path));
+        let read_only = limit.is_some() || decoded.is_none() || unwritable;
+        let decoded = match decoded {
+            Some(decoded) => decoded,
+            None => match self.confirm_binary(path) {
+                wx::YES => encoding::decode_lossy(&bytes),
+                wx::NO => Decoded {
+                    text: hex::dump(&bytes),
+                    format: TextFormat::default(),
+                    lossy: true,
+                },
+                _ => return false,
+            },
+        };
+        self.editor.set_contents(Some(path), &decoded);
+        self.editor.set_cleanup(default_cleanup(&settings));
+        self.partial.set(limit.is_some());
+        self.set_read_only(read_only);
+        self.update_indent_status();
+        if let Some(limit) = limit {
+            self.base.set_status_text(
+                &format!(
+                    "先頭の {} MB だけを読み取り専用で表示しています。",
+                    limit / file_loader::MB
+                ),
+                STATUS_MESSAGE,
+            );
+        } else if unwritable {
+            self.base.set_status_text(
+                "書き込みが許可されていないため、読み取り専用で開きました。",
+                STATUS_MESSAGE,
+            );
+        }
+        true
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.editor.set_read_only(read_only);
+        self.update_title();
+    }
+
+    /// 読み取り専用を切り替えます。書き込めないファイルなら、
+    /// 書き込めるようにするか、別の名前で保存するかを尋ねます。
+    fn toggle_read_only(&self) {
+        if !self.editor.is_read_only() {
+            self.set_read_only(true);
+            return;
+        }
+        if self.partial.get() {
+            self.show_error(
+                "ファイルの一部だけを読み込んでいるため、編集できません。\n\
+                 編集するには、ファイル全体を開き直してください。",
+            );
+            return;
+        }
+        if let Some(path) = self.editor.path() {
+            if !file_loader::is_writable(Path::new(&path)) && !self.resolve_unwritable(&path) {
                 return;
             }
-            let file_dialog = wx::FileDialog::builder(Some(&self.base)).build();
-            if wx::ID_OK == file_dialog.show_modal() {
-                let path = file_dialog.get_path();
-                editor.load_from(&path);
+        }
+        self.set_read_only(false);
+    }
+
+    fn resolve_unwritable(&self, path: &str) -> bool {
+        let dialog = wx::MessageDialog::builder(Some(&self.base))
+            .message(&format!(
+                "{} には書き込みが許可されていません。\n\n\
+                 ファイルを書き込み可能にするか、別の名前で保存してください。",
+                path
+            ))
+            .caption(APP_NAME)
+            .style((wx::YES_NO | wx::CANCEL | wx::ICON_QUESTION).into())
+            .build();
+        dialog.set_yes_no_cancel_labels(
+            "書き込み可能にする(&W)",
+            "名前を付けて保存(&A)…",
+            "キャンセル",
+        );
+        match dialog.show_modal() {
+            wx::ID_YES => match file_loader::make_writable(Path::new(path)) {
+                Ok(()) => true,
+                Err(e) => {
+                    self.show_error(&format!("{} を書き込み可能にできません。\n{}", path, e));
+                    false
+                }
+            },
+            wx::ID_NO => self.save_as().is_ok(),
+            _ => false,
+        }
+    }
+
+    /// バイナリファイルの開き方を尋ねます。
+    /// 読み取り専用なら `wx::YES`、16 進数表示なら `wx::NO` を返します。
+    fn confirm_binary(&self, path: &str) -> c_int {
+        wx::message_box(
+            &format!(
+                "{} はテキストファイルではないようです。\n\n\
+                 読み取り専用で開くには「はい」を、\
+                 16 進数で表示するには「いいえ」を選んでください。",
+                path
+            ),
+            APP_NAME,
+            wx::YES_NO | (wx::CANCEL | wx::ICON_WARNING | wx::CENTRE) as c_int,
+            Some(&self.base),
+        )
+    }
+
+    /// 大きなファイルは別スレッドで読み込み、進捗ダイアログを表示します。
+    fn read_with_progress(
+        &self,
+        path: &str,
+        size: u64,
+        limit: Option<u64>,
+    ) -> Result<Vec<u8>, LoadError> {
+        let total = limit.map_or(size, |limit| limit.min(size));
+        let progress = Arc::new(AtomicU64::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        if total < PROGRESS_MIN_SIZE {
+            return file_loader::read(Path::new(path), limit, &progress, &cancel);
+        }
+
+        let worker = {
+            let path = PathBuf::from(path);
+            let progress = progress.clone();
+            let cancel = cancel.clone();
+            thread::spawn(move || file_loader::read(&path, limit, &progress, &cancel))
+        };
+        let dialog = wx::ProgressDialog::new(
+            APP_NAME,
+            &format!("{} を読み込んでいます…", path),
+            100,
+            Some(&self.base),
+            wx::PD_APP_MODAL | wx::PD_CAN_ABORT | wx::PD_ELAPSED_TIME | wx::PD_REMAINING_TIME,
+        );
+        while !worker.is_finished() {
+            let percent = progress.load(Ordering::Relaxed) * 100 / total;
+            if !dialog.update(percent as c_int, "", ptr::null_mut()) {
+                // キャンセルボタンが押された
+                cancel.store(true, Ordering::Relaxed);
             }
-        });
+            thread::sleep(Duration::from_millis(50));
+        }
+        dialog.destroy();
+        worker.join().unwrap_or(Err(LoadError::Cancelled))
+    }
+
+    fn show_error(&self, message: &str) {
+        wx::message_box(
+            message,
+            APP_NAME,
+            (wx::OK | wx::ICON_ERROR | wx::CENTRE) as c_int,
+            Some(&self.base),
+        );
     }
 
     pub fn save(&self) -> Result<(), ()> {
@@ -166,59 +555,675 @@ impl EditorFrame {
         }
     }
 
+    /// 内容を別のファイルに書き出します。編集中のファイルはそのままです。
+    pub fn save_copy_as(&self) {
+        let file_dialog = wx::FileDialog::builder(Some(&self.base))
+            .message("コピーを保存")
+            .style((wx::FC_SAVE | wx::FC_OVERWRITE_PROMPT).into())
+            .build();
+        if wx::ID_OK != file_dialog.show_modal() {
+            return;
+        }
+        let path = file_dialog.get_path();
+        if unsaved_changes::save_copy_to(&self.editor, &self.base, &path) {
+            self.base
+                .set_status_text(&format!("コピーを保存しました: {}", path), STATUS_MESSAGE);
+        } else {
+            self.show_error(&format!("{} に保存できませんでした。", path));
+        }
+    }
+
+    /// 変更を破棄して、保存されている内容を読み込み直します。
+    pub fn revert(&self) {
+        let path = match self.editor.path() {
+            Some(path) => path,
+            None => return,
+        };
+        if self.editor.is_modified() {
+            let answer = wx::message_box(
+                &format!(
+                    "{} の変更を破棄して、保存されている内容を読み込み直しますか?",
+                    path
+                ),
+                APP_NAME,
+                wx::YES_NO | (wx::NO_DEFAULT | wx::ICON_WARNING | wx::CENTRE) as c_int,
+                Some(&self.base),
+            );
+            if answer != wx::YES {
+                return;
+            }
+        }
+        let caret = self.editor.caret();
+        self.load_file(&path);
+        self.editor.set_caret(caret);
+    }
+
     fn save_to(&self, path: &str) -> Result<(), ()> {
         // TODO: Error Handling
-        if self.editor.save_to(&path) {
+        if unsaved_changes::save_to(&self.editor, &self.base, path) {
             Ok(())
         } else {
             Err(())
         }
     }
 
+    pub fn page_setup(&self) {
+        let dialog = wx::PageSetupDialog::new(Some(&self.base), Some(&*self.page_setup.borrow()));
+        if wx::ID_OK != dialog.show_modal() {
+            return;
+        }
+        *self.page_setup.borrow_mut() = dialog.get_page_setup_data();
+        // wx のページ設定ダイアログにはヘッダーとフッターの欄がないため、
+        // 続けて入力してもらいます。
+        let codes = "(&f ファイル名, &p ページ, &d 日付, &t 時刻, &l/&c/&r 左/中央/右)";
+        // キャンセルしたら元のままにします。空にしたときと区別するため、
+        // wx::get_text_from_user() ではなくダイアログの結果を見ます。
+        if let Some(header) = self.ask_header_text(&format!("ヘッダー {}", codes), &self.header)
+        {
+            *self.header.borrow_mut() = header;
+        }
+        if let Some(footer) = self.ask_header_text(&format!("フッター {}", codes), &self.footer)
+        {
+            *self.footer.borrow_mut() = footer;
+        }
+    }
+
+    fn ask_header_text(&self, message: &str, current: &RefCell<String>) -> Option<String> {
+        let dialog = wx::TextEntryDialog::builder(Some(&self.base))
+            .message(message)
+            .caption("ページ設定")
+            .value(&current.borrow())
+            .build();
+        if dialog.show_modal() != wx::ID_OK {
+            return None;
+        }
+        Some(dialog.get_value())
+    }
+
+    fn new_printout(&self) -> wx::Printout {
+        let file_name = self
+            .editor
+            .path()
+            .as_deref()
+            .and_then(|path| Path::new(path).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| UNTITLED.to_owned());
+        printing::TextPrintout::new(
+            self.editor.text(),
+            self.editor.font(),
+            self.editor.word_wrap(),
+            &file_name,
+            &self.header.borrow(),
+            &self.footer.borrow(),
+            &self.page_setup.borrow(),
+        )
+        .create()
+    }
+
+    pub fn print_preview(&self) {
+        let print_data = self.page_setup.borrow().get_print_data();
+        let preview = wx::PrintPreview::new(
+            Some(&self.new_printout()),
+            Some(&self.new_printout()),
+            Some(&print_data),
+        );
+        if !preview.is_ok() {
+            self.show_error("印刷プレビューを表示できません。プリンターの設定を確認してください。");
+            return;
+        }
+        let preview_frame =
+            wx::PreviewFrame::new(Some(&preview), Some(&self.base), "印刷プレビュー");
+        preview_frame.initialize();
+        preview_frame.show(true);
+    }
+
+    pub fn print(&self) {
+        let print_data = self.page_setup.borrow().get_print_data();
+        let dialog_data = wx::PrintDialogData::new_with_printdata(&print_data);
+        let printer = wx::Printer::new(Some(&dialog_data));
+        if printer.print(Some(&self.base), Some(&self.new_printout()), true) {
+            // 印刷ダイアログで選んだプリンターを次回も使います。
+            self.page_setup
+                .borrow_mut()
+                .set_print_data(&printer.get_print_dialog_data().get_print_data());
+        } else if wx::Printer::get_last_error() == wx::PRINTER_ERROR {
+            self.show_error("印刷できませんでした。");
+        }
+    }
+
+    fn show_find_dialog(&self, replace: bool) {
+        let mut find_dialog = self.find_dialog.borrow_mut();
+        let dialog = find_dialog.get_or_insert_with(|| {
+            let this = self.this.clone();
+            FindDialog::new(&self.base, move |action| {
+                if let Some(frame) = this.upgrade() {
+                    frame.on_find_action(action);
+                }
+            })
+        });
+        let mut query = self.find_query.borrow().clone();
+        let scope = self.editor.selection_range();
+        let selected = self.editor.selected_text();
+        match selected {
+            // 複数行の選択は、選択範囲内を検索するものとみなします。
+            Some(selected) if selected.contains('\n') => query.options.in_selection = true,
+            // メモ帳と同じく、選択中の文字列を検索する文字列にします。
+            Some(selected) if !query.options.in_selection => query.pattern = selected,
+            _ => (),
+        }
+        let has_selection = !scope.is_empty();
+        *self.find_scope.borrow_mut() = Some(scope).filter(|_| has_selection);
+        dialog.show(replace, &query, has_selection);
+    }
+
+    fn searcher(&self, query: &FindQuery) -> Result<Searcher, InvalidPattern> {
+        let searcher = Searcher::new(query)?;
+        match self.find_scope.borrow().clone() {
+            Some(scope) if query.options.in_selection => Ok(searcher.with_scope(scope)),
+            _ => Ok(searcher),
+        }
+    }
+
+    fn on_find_action(&self, action: FindAction) {
+        let dialog = match self.find_dialog.borrow().clone() {
+            Some(dialog) => dialog,
+            None => return,
+        };
+        let query = dialog.query();
+        *self.find_query.borrow_mut() = query.clone();
+        let searcher = match self.searcher(&query) {
+            Ok(searcher) => searcher,
+            Err(e) => {
+                dialog.set_error(Some(&e.to_string()));
+                return;
+            }
+        };
+        dialog.set_error(None);
+        let is_replace = matches!(action, FindAction::Replace | FindAction::ReplaceAll);
+        if is_replace && self.editor.is_read_only() {
+            dialog.set_error(Some("読み取り専用のため置換できません。"));
+            return;
+        }
+        let scope = self.find_scope.borrow().clone();
+        if let Some(scope) = scope.filter(|_| query.options.in_selection) {
+            // 範囲全体が選択されたままなら、範囲の端から探し始めます。
+            if self.editor.selection_range() == scope {
+                let caret = match action {
+                    FindAction::FindPrevious => scope.end,
+                    _ => scope.start,
+                };
+                self.editor.select_range(caret..caret);
+            }
+        }
+        let length = self.editor.text().len();
+        let found = match action {
+            FindAction::FindNext => self.editor.find(&searcher, Direction::Forward),
+            FindAction::FindPrevious => self.editor.find(&searcher, Direction::Backward),
+            FindAction::Replace => {
+                if self.editor.replace(&searcher, &query.replacement) {
+                    self.adjust_find_scope(length);
+                }
+                // 範囲が変わったかもしれないので、検索し直す前に作り直します。
+                match self.searcher(&query) {
+                    Ok(searcher) => self.editor.find(&searcher, Direction::Forward),
+                    Err(_) => false,
+                }
+            }
+            FindAction::ReplaceAll => {
+                let count = self.editor.replace_all(&searcher, &query.replacement);
+                self.adjust_find_scope(length);
+                self.base
+                    .set_status_text(&format!("{} 個置換しました。", count), STATUS_MESSAGE);
+                count > 0
+            }
+        };
+        if !found {
+            self.show_not_found(&query.pattern);
+        }
+    }
+
+    /// 置換で長さが変わった分だけ、選択範囲内で検索する範囲の末尾をずらします。
+    fn adjust_find_scope(&self, old_length: usize) {
+        let new_length = self.editor.text().len();
+        if let Some(scope) = self.find_scope.borrow_mut().as_mut() {
+            scope.end = (scope.end + new_length).saturating_sub(old_length);
+        }
+    }
+
+    fn find_again(&self, direction: Direction) {
+        let query = self.find_query.borrow().clone();
+        if query.pattern.is_empty() {
+            self.show_find_dialog(false);
+            return;
+        }
+        match self.searcher(&query) {
+            Ok(searcher) => {
+                if !self.editor.find(&searcher, direction) {
+                    self.show_not_found(&query.pattern);
+                }
+            }
+            Err(e) => self.show_error(&e.to_string()),
+        }
+    }
+
+    fn show_find_bar(&self) {
+        let selection = self.editor.selection_range();
+        self.find_bar_anchor.set(selection.start);
+        let selected = self.editor.selected_text().filter(|s| !s.contains('\n'));
+        self.find_bar.show(selected.as_deref());
+        self.base.layout();
+        self.refresh_find_bar(true);
+    }
+
+    fn close_find_bar(&self) {
+        self.find_bar.hide();
+        self.editor.clear_highlight();
+        self.base.layout();
+        self.editor.set_focus();
+    }
+
+    fn on_find_bar(&self, event: FindBarEvent) {
+        match event {
+            FindBarEvent::Changed => self.refresh_find_bar(true),
+            FindBarEvent::FindNext => self.step_find_bar(Direction::Forward),
+            FindBarEvent::FindPrevious => self.step_find_bar(Direction::Backward),
+            FindBarEvent::Close => self.close_find_bar(),
+        }
+    }
+
+    /// クイック検索の条件。大文字と小文字の区別などは検索ダイアログに合わせます。
+    fn find_bar_query(&self) -> FindQuery {
+        let options = self.find_query.borrow().options.clone();
+        FindQuery {
+            pattern: self.find_bar.pattern(),
+            replacement: String::new(),
+            options: SearchOptions {
+                wrap_around: true,
+                in_selection: false,
+                ..options
+            },
+        }
+    }
+
+    /// 一致したところすべてに色を付け直します。
+    /// `select` が真なら、検索を始めた位置から最初の一致を選択します。
+    fn refresh_find_bar(&self, select: bool) {
+        let query = self.find_bar_query();
+        if query.pattern.is_empty() {
+            self.editor.clear_highlight();
+            self.find_bar.set_status("");
+            return;
+        }
+        let searcher = match Searcher::new(&query) {
+            Ok(searcher) => searcher,
+            Err(e) => {
+                self.editor.clear_highlight();
+                self.find_bar.set_status(&e.to_string());
+                return;
+            }
+        };
+        let matches = searcher.find_all(&self.editor.text());
+        // RichEdit では色付けも元に戻す操作に入るので、入力中は付け直しません。
+        if select || !self.editor.highlight_is_undoable() {
+            let anchor = self.editor.selection_range().start;
+            self.editor
+                .highlight(search::nearby(&matches, anchor, MAX_HIGHLIGHTS));
+        }
+        if select {
+            if let Some(i) = search::first_from(&matches, self.find_bar_anchor.get()) {
+                self.editor.select_range(matches[i].clone());
+            }
+        }
+        self.update_find_bar_status(&matches);
+    }
+
+    fn step_find_bar(&self, direction: Direction) {
+        let query = self.find_bar_query();
+        if query.pattern.is_empty() {
+            return;
+        }
+        let searcher = match Searcher::new(&query) {
+            Ok(searcher) => searcher,
+            Err(_) => return,
+        };
+        // F3 でも同じ文字列を検索できるようにします。
+        self.find_query.borrow_mut().pattern = query.pattern.clone();
+        if !self.editor.find(&searcher, direction) {
+            wx::bell();
+        }
+        self.find_bar_anchor
+            .set(self.editor.selection_range().start);
+        self.update_find_bar_status(&searcher.find_all(&self.editor.text()));
+    }
+
+    fn update_find_bar_status(&self, matches: &[Range<usize>]) {
+        let current = search::index_of(matches, &self.editor.selection_range());
+        let status = match (matches.len(), current) {
+            (0, _) => "見つかりません".to_owned(),
+            (count, Some(i)) => format!("{} / {} 件", i + 1, count),
+            (count, None) => format!("{} 件", count),
+        };
+        self.find_bar.set_status(&status);
+    }
+
+    fn show_not_found(&self, pattern: &str) {
+        wx::message_box(
+            &format!("\"{}\" が見つかりません。", pattern),
+            APP_NAME,
+            (wx::OK | wx::ICON_INFORMATION | wx::CENTRE) as c_int,
+            Some(&self.base),
+        );
+    }
+
     pub fn close(&self) {
         self.base.close(false);
     }
 
-    pub fn on_update_ui(
-        &self,
-        event: &wx::UpdateUIEvent,
-        command: &EditorCommand<wx::UpdateUIEvent>,
-    ) {
-        match command {
-            EditorCommand::Command(command) => match &command {
-                // ファイル
-                Command::FileNewWindow
-                // 編集
-                | Command::EditFind
-                | Command::EditFindNext
-                | Command::EditFindPrevious
-                | Command::EditReplace
-                | Command::EditGo
-                | Command::EditDate
-                // 書式
-                | Command::FormatWordWrap | Command::FormatFont
-                // 表示
-                | Command::ViewStatusBar => {
-                    event.enable(false);
+    pub fn on_update_ui(&self, event: &wx::UpdateUIEvent) {
+        self.commands.borrow().update_ui(self, event);
+    }
+
+    /// 選択範囲の行、選択していなければ文書全体の行頭のタブと空白を変換します。
+    fn convert_indent(&self, to_spaces: bool) {
+        let tab_width = self.editor.tab_width();
+        if to_spaces {
+            self.editor
+                .transform_lines(|text| text_transform::tabs_to_spaces(text, tab_width));
+        } else {
+            self.editor
+                .transform_lines(|text| text_transform::spaces_to_tabs(text, tab_width));
+        }
+        self.update_indent_status();
+    }
+
+    fn toggle_line_numbers(&self) {
+        let visible = !self.editor.line_numbers();
+        self.editor.set_line_numbers(visible);
+        Settings::update(|s| s.set(settings::LINE_NUMBERS, visible));
+    }
+
+    fn set_language_choice(&self, choice: syntax::Choice) {
+        self.editor.set_language_choice(choice);
+        self.update_preview();
+    }
+
+    fn is_markdown(&self) -> bool {
+        self.editor
+            .language()
+            .is_some_and(|language| language.lexer == wx::STC_LEX_MARKDOWN)
+    }
+
+    /// Markdown の文書ならプレビューを表示し、内容が変わったら表示し直します。
+    fn update_preview(&self) {
+        let visible = self.preview_enabled.get() && self.is_markdown();
+        if visible != self.preview.is_shown() {
+            self.preview.show(visible);
+            self.base.layout();
+            if visible {
+                self.refresh_preview();
+            }
+        } else if visible {
+            self.preview.schedule_refresh();
+        }
+    }
+
+    fn refresh_preview(&self) {
+        self.preview
+            .render(&self.editor.text(), self.editor.path().as_deref());
+        self.preview.sync_scroll(self.editor.first_visible_line());
+    }
+
+    fn toggle_markdown_preview(&self) {
+        let enabled = !self.preview.is_shown();
+        self.preview_enabled.set(enabled);
+        self.update_preview();
+        Settings::update(|s| s.set(settings::MARKDOWN_PREVIEW, enabled));
+    }
+
+    fn toggle_indent_with_spaces(&self) {
+        let indent_with_spaces = !self.editor.indent_with_spaces();
+        self.editor.set_indent_with_spaces(indent_with_spaces);
+        Settings::update(|s| s.set(settings::INDENT_WITH_SPACES, indent_with_spaces));
+    }
+
+    fn set_tab_width(&self, tab_width: usize) {
+        self.editor.set_tab_width(tab_width);
+        Settings::update(|s| s.set(settings::TAB_WIDTH, tab_width));
+    }
+
+    /// 今の文書の空白の整理方法を変更します。
+    fn update_cleanup<F: FnOnce(&mut Cleanup)>(&self, f: F) {
+        let mut cleanup = self.editor.cleanup();
+        f(&mut cleanup);
+        self.editor.set_cleanup(cleanup);
+    }
+
+    fn toggle_final_newline_on_save(&self, final_newline: FinalNewline) {
+        self.update_cleanup(|c| {
+            c.final_newline = if c.final_newline == final_newline {
+                FinalNewline::Keep
+            } else {
+                final_newline
+            };
+        });
+    }
+
+    /// 保存時と同じ整理を今すぐ行います。全角空白を対象にするかは文書の設定に従います。
+    fn clean_up_now(&self, trim_trailing_whitespace: bool, final_newline: FinalNewline) {
+        self.editor.clean_up_whitespace(Cleanup {
+            trim_trailing_whitespace,
+            trim_full_width_space: self.editor.cleanup().trim_full_width_space,
+            final_newline,
+        });
+    }
+
+    fn show_statistics(&self) {
+        let format = self.editor.text_format();
+        let text = self.editor.text();
+        let whole = Statistics::of_document(&text, format);
+        let selection = self.editor.selection_range();
+        let selection = if selection.is_empty() {
+            None
+        } else {
+            Some(Statistics::of_selection(&text[selection], format))
+        };
+        statistics::show(&self.base, &format.name(), &whole, selection.as_ref());
+    }
+
+    /// ディスク上のファイルを 16 進数で表示します。編集して保存したら、読み込み直します。
+    fn show_hex(&self) {
+        let path = match self.editor.path() {
+            Some(path) => path,
+            None => return,
+        };
+        // テキストの表示とそろえるため、先に変更を保存するか尋ねます。
+        unsaved_changes::save(&self.editor, &self.base, |_, saved| {
+            if !saved {
+                return;
+            }
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    self.show_error(&format!("{} を読み込めません。\n{}", path, e));
+                    return;
                 }
-                _ => (),
-            },
-            EditorCommand::StandardEvents(command) => {
-                self.editor.on_update_ui(command);
+            };
+            if bytes.len() as u64 > hex::MAX_MB * file_loader::MB {
+                self.show_error(&format!(
+                    "16 進数で表示できるのは {} MB までのファイルです。",
+                    hex::MAX_MB
+                ));
+                return;
             }
+            // 読み取り専用で開いているときは、16 進数でも表示だけにします。
+            // バイナリファイルを編集するには、先に読み取り専用を解除します。
+            let editable =
+                !self.editor.is_read_only() && file_loader::is_writable(Path::new(&path));
+            let bytes = match hex::edit(&self.base, &path, bytes, editable) {
+                Some(bytes) => bytes,
+                None => return,
+            };
+            if let Err(e) = fs::write(&path, bytes) {
+                self.show_error(&format!("{} に書き込めません。\n{}", path, e));
+                return;
+            }
+            let caret = self.editor.caret();
+            self.load_file(&path);
+            self.editor.set_caret(caret);
+        });
+    }
+
+    fn show_command_palette(&self) {
+        let entries = self
+            .commands
+            .borrow()
+            .iter()
+            .filter(|spec| spec.id() != c_int::from(Command::ViewCommandPalette))
+            .map(|spec| PaletteEntry {
+                id: spec.id(),
+                label: command_palette::strip_mnemonic(spec.label()),
+                shortcut: spec.accel_text().unwrap_or_default().to_owned(),
+                enabled: spec.is_enabled(self),
+            })
+            .collect();
+        if let Some(id) = command_palette::choose(&self.base, entries) {
+            // メニューから選んだときと同じ経路で handle_command() に渡します。
+            self.base.process_command(id);
         }
     }
 
     pub fn on_close(&self, event: &wx::CloseEvent) {
+        // 最後のウィンドウを閉じるとアプリが終了するので、そのときだけセッションを保存します。
+        // 途中で閉じたウィンドウは、次の起動で復元しません。
+        let quitting = open_frames()
+            .iter()
+            .all(|frame| ptr::eq(frame.as_ref(), self));
+        if quitting && self.can_hot_exit() {
+            // 確認せずに内容を退避して終了します。退避できなければ確認します。
+            let previous = self.backup.borrow().clone();
+            if let Ok(backup) = session::write_backup(previous.as_deref(), &self.editor.text()) {
+                *self.backup.borrow_mut() = Some(backup);
+                save_session();
+                self.closed();
+                event.skip(true);
+                return;
+            }
+        }
         unsaved_changes::save(&self.editor, &self.base, |_, saved| {
             if !saved {
                 event.veto(true);
                 return;
             }
+            // 保存するか破棄したので、退避した内容はもう要りません。
+            if let Some(old) = self.backup.take() {
+                session::remove_backup(&old);
+            }
+            if quitting {
+                save_session();
+            }
+            self.closed();
             event.skip(true);
         });
     }
 
+    /// 閉じたウィンドウの表示倍率を保存し、開いているウィンドウの一覧から外します。
+    fn closed(&self) {
+        let zoom = self.editor.zoom();
+        Settings::update(|s| s.set(settings::ZOOM, zoom));
+        OPEN_FRAMES.with(|frames| {
+            frames
+                .borrow_mut()
+                .retain(|frame| frame.upgrade().is_some_and(|f| !ptr::eq(f.as_ref(), self)))
+        });
+    }
+
+    fn can_hot_exit(&self) -> bool {
+        self.hot_exit.get() && self.editor.path().is_none() && self.editor.is_modified()
+    }
+
+    fn toggle_hot_exit(&self) {
+        let hot_exit = !self.hot_exit.get();
+        self.hot_exit.set(hot_exit);
+        Settings::update(|s| s.set(settings::HOT_EXIT, hot_exit));
+    }
+
+    fn sort_lines(&self, descending: bool) {
+        let options = SortOptions {
+            descending,
+            ..self.sort_options.get()
+        };
+        self.editor
+            .transform_lines(|text| lines::sort(text, options));
+    }
+
+    fn toggle_sort_ignore_case(&self) {
+        let mut options = self.sort_options.get();
+        options.ignore_case = !options.ignore_case;
+        self.sort_options.set(options);
+        Settings::update(|s| s.set(settings::SORT_IGNORE_CASE, options.ignore_case));
+    }
+
+    fn toggle_sort_numeric(&self) {
+        let mut options = self.sort_options.get();
+        options.numeric = !options.numeric;
+        self.sort_options.set(options);
+        Settings::update(|s| s.set(settings::SORT_NUMERIC, options.numeric));
+    }
+
+    fn window_state(&self) -> WindowState {
+        let rect = self.base.get_rect();
+        WindowState {
+            path: self.editor.path(),
+            rect: Some((
+                rect.get_x(),
+                rect.get_y(),
+                rect.get_width(),
+                rect.get_height(),
+            )),
+            maximized: self.base.is_maximized(),
+            caret: self.editor.caret(),
+            scroll: self.editor.first_visible_line(),
+            word_wrap: self.editor.word_wrap(),
+            backup: self.backup.borrow().clone(),
+        }
+    }
+
+    /// 前回終了したときのウィンドウの状態と内容を復元します。
+    pub fn restore(&self, state: &WindowState) {
+        if let Some((x, y, width, height)) = state.rect {
+            self.base.set_size_int(x, y, width, height, wx::SIZE_AUTO);
+        }
+        if state.maximized {
+            self.base.maximize(true);
+        }
+        self.editor.set_word_wrap(state.word_wrap);
+        if let Some(backup) = &state.backup {
+            match fs::read(backup) {
+                Ok(bytes) => {
+                    let decoded = encoding::decode_lossy(&bytes);
+                    self.editor.restore_unsaved(&decoded.text);
+                    self.update_indent_status();
+                    *self.backup.borrow_mut() = Some(backup.clone());
+                }
+                Err(e) => {
+                    self.show_error(&format!(
+                        "保存していない内容を復元できませんでした。\n{}",
+                        e
+                    ));
+                    return;
+                }
+            }
+        } else if let Some(path) = &state.path {
+            // 前回から消えたファイルは開きません。
+            if !Path::new(path).exists() {
+                return;
+            }
+            self.load_file(path);
+        }
+        self.editor.set_caret(state.caret);
+        self.editor.scroll_to_line(state.scroll);
+    }
+
     pub fn open_help(&self) {
         let project_home = "https://github.com/kenz-gelsoft/ClawEditor/";
         wx::launch_default_browser(project_home, 0);
@@ -246,66 +1251,524 @@ impl EditorFrame {
         if self.editor.is_modified() {
             modified = "*";
         }
-        let title = format!("{}{} - {}", modified, file, APP_NAME);
+        let read_only = if self.editor.is_read_only() {
+            " [読み取り専用]"
+        } else {
+            ""
+        };
+        let title = format!("{}{}{} - {}", modified, file, read_only, APP_NAME);
         self.base.set_title(&title);
     }
 }
-impl<'a> CommandHandler<EditorCommand<'a, wx::CommandEvent>> for EditorFrame {
-    fn handle_command(&self, editor_command: &EditorCommand<'a, wx::CommandEvent>) {
-        match editor_command {
-            EditorCommand::Command(command) => match &command {
-                // ファイル
-                Command::FileNew => {
-                    self.new_file();
-                }
-                Command::FileNewWindow => todo!(),
-                Command::FileOpen => {
-                    self.open_file(None);
-                }
-                Command::FileSave => {
-                    _ = self.save();
-                }
-                Command::FileSaveAs => {
-                    _ = self.save_as();
-                }
-                Command::FileClose => {
-                    self.close();
-                }
-                // 編集
-                Command::EditFind => todo!(),
-                Command::EditFindNext => todo!(),
-                Command::EditFindPrevious => todo!(),
-                Command::EditReplace => todo!(),
-                Command::EditGo => todo!(),
-                Command::EditDate => todo!(),
-                // 書式
-                Command::FormatWordWrap => todo!(),
-                Command::FormatFont => todo!(),
-                // 表示
-                Command::ViewStatusBar => todo!(),
-                // 書式
-                Command::Help => {
-                    self.open_help();
-                }
-                Command::EditDelete => {
-                    self.editor.handle_command(editor_command);
-                }
-            },
-            EditorCommand::StandardEvents(event) => match event.get_id() {
-                wx::ID_ABOUT => {
-                    self.show_about();
-                }
-                wx::ID_EXIT => {
-                    // Mac で終了したとき
-                    self.close();
-                }
-                _ => {
-                    self.editor.handle_command(editor_command);
-                }
-            },
+impl CommandHandler<wx::CommandEvent> for EditorFrame {
+    fn handle_command(&self, event: &wx::CommandEvent) {
+        // ハンドラの中でコマンドを追加できるよう、借用を解いてから呼び出します。
+        let handler = self.commands.borrow().handler(event.get_id());
+        if let Some(handler) = handler {
+            handler(self, event);
+            return;
+        }
+        match event.get_id() {
+            wx::ID_EXIT => {
+                // Mac で終了したとき
+                self.close();
+            }
+            _ => {
+                forward_to_editor(self, event);
+            }
         }
     }
 }
+
+type Spec = CommandSpec<EditorFrame>;
+
+fn forward_to_editor(frame: &EditorFrame, event: &wx::CommandEvent) {
+    frame
+        .editor
+        .handle_command(&EditorCommand::StandardEvents(event));
+}
+
+/// `command` を `EditorCtrl` に渡して実行するハンドラを返します。
+fn editor_command(command: Command) -> impl Fn(&EditorFrame, &wx::CommandEvent) {
+    move |frame, _| {
+        frame
+            .editor
+            .handle_command(&EditorCommand::Command(command))
+    }
+}
+
+/// 新しく開いた文書に使う、空白の整理方法の既定値
+fn default_cleanup(config: &Settings) -> Cleanup {
+    Cleanup {
+        trim_trailing_whitespace: config
+            .get(settings::TRIM_TRAILING_WHITESPACE)
+            .unwrap_or(false),
+        trim_full_width_space: config.get(settings::TRIM_FULL_WIDTH_SPACE).unwrap_or(false),
+        final_newline: config.get(settings::FINAL_NEWLINE).unwrap_or_default(),
+    }
+}
+
+fn is_editable(frame: &EditorFrame) -> bool {
+    !frame.editor.is_read_only()
+}
+
+/// ファイル全体を読み込んでいれば真を返します。
+/// 先頭だけを読み込んだ内容を書き出すと、元のファイルに上書きしたときに後ろが失われます。
+fn is_whole_file(frame: &EditorFrame) -> bool {
+    !frame.partial.get()
+}
+
+/// 選択範囲を書き換えるコマンドを実行できるなら真を返します。
+fn can_convert(frame: &EditorFrame) -> bool {
+    is_editable(frame) && frame.editor.has_selection()
+}
+
+fn builtin_commands() -> CommandRegistry<EditorFrame> {
+    let mut registry = CommandRegistry::new();
+    for spec in [
+        // ファイル
+        Spec::new(Command::FileNew, "新規(&N)")
+            .accel("Ctrl-N")
+            .menu(MenuId::File)
+            .handler(|frame, _| frame.new_file()),
+        Spec::new(Command::FileNewWindow, "新しいウィンドウ(&W)")
+            .accel("Ctrl-Shift-N")
+            .menu(MenuId::File),
+        Spec::new(Command::FileOpen, "開く(&W)…")
+            .accel("Ctrl-O")
+            .menu(MenuId::File)
+            .handler(|frame, _| frame.open_file(None)),
+        Spec::new(Command::FileSave, "保存(&S)")
+            .accel("Ctrl-S")
+            .menu(MenuId::File)
+            .enabled(is_editable)
+            .handler(|frame, _| {
+                _ = frame.save();
+            }),
+        Spec::new(Command::FileSaveAs, "名前を付けて保存(&A)…")
+            .accel("Ctrl-Shift-S")
+            .menu(MenuId::File)
+            .enabled(is_whole_file)
+            .handler(|frame, _| {
+                _ = frame.save_as();
+            }),
+        Spec::new(Command::FileSaveCopyAs, "コピーを保存(&Y)…")
+            .menu(MenuId::File)
+            .enabled(is_whole_file)
+            .handler(|frame, _| frame.save_copy_as()),
+        Spec::new(Command::FileRevert, "保存した状態に戻す(&E)")
+            .menu(MenuId::File)
+            .enabled(|frame| frame.editor.path().is_some())
+            .handler(|frame, _| frame.revert()),
+        Spec::new(Command::FileReadOnly, "読み取り専用(&R)")
+            .menu(MenuId::File)
+            .separator()
+            .checked(|frame| frame.editor.is_read_only())
+            .handler(|frame, _| frame.toggle_read_only()),
+        Spec::new(Command::FilePageSetup, "ページ設定(&U)…")
+            .menu(MenuId::File)
+            .separator()
+            .handler(|frame, _| frame.page_setup()),
+        Spec::new(Command::FilePrintPreview, "印刷プレビュー(&V)")
+            .menu(MenuId::File)
+            .handler(|frame, _| frame.print_preview()),
+        Spec::new(Command::FilePrint, "印刷(&P)…")
+            .accel("Ctrl-P")
+            .menu(MenuId::File)
+            .handler(|frame, _| frame.print()),
+        Spec::new(Command::FileHotExit, "終了時に保存していない内容を保持(&K)")
+            .menu(MenuId::File)
+            .separator()
+            .checked(|frame| frame.hot_exit.get())
+            .handler(|frame, _| frame.toggle_hot_exit()),
+        Spec::new(Command::FileClose, "終了(&X)")
+            .accel("Ctrl-W")
+            .menu(MenuId::File)
+            .separator()
+            .handler(|frame, _| frame.close()),
+        // 編集
+        Spec::new(wx::ID_UNDO, "元に戻す(&U)")
+            .accel("Ctrl-Z")
+            .menu(MenuId::Edit)
+            .enabled(|frame| !frame.editor.is_read_only() && frame.editor.can_undo())
+            .handler(forward_to_editor),
+        Spec::new(wx::ID_CUT, "切り取り(&T)")
+            .accel("Ctrl-X")
+            .menu(MenuId::Edit)
+            .separator()
+            .enabled(is_editable)
+            .handler(forward_to_editor),
+        Spec::new(wx::ID_COPY, "コピー(&C)")
+            .accel("Ctrl-C")
+            .menu(MenuId::Edit)
+            .handler(forward_to_editor),
+        Spec::new(wx::ID_PASTE, "貼り付け(&P)")
+            .accel("Ctrl-V")
+            .menu(MenuId::Edit)
+            .enabled(is_editable)
+            .handler(forward_to_editor),
+        Spec::new(Command::EditDelete, "削除(&L)")
+            .accel("Del")
+            .menu(MenuId::Edit)
+            .enabled(is_editable)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .handle_command(&EditorCommand::Command(Command::EditDelete))
+            }),
+        Spec::new(Command::EditFind, "検索(&F)…")
+            .accel("Ctrl-F")
+            .menu(MenuId::Edit)
+            .separator()
+            .handler(|frame, _| frame.show_find_dialog(false)),
+        Spec::new(Command::EditIncrementalFind, "クイック検索(&I)")
+            .accel("Ctrl-Shift-F")
+            .menu(MenuId::Edit)
+            .handler(|frame, _| frame.show_find_bar()),
+        Spec::new(Command::EditFindNext, "次を検索(&N)")
+            .accel("F3")
+            .menu(MenuId::Edit)
+            .handler(|frame, _| frame.find_again(Direction::Forward)),
+        Spec::new(Command::EditFindPrevious, "前を検索(&V)")
+            .accel("Shift-F3")
+            .menu(MenuId::Edit)
+            .handler(|frame, _| frame.find_again(Direction::Backward)),
+        Spec::new(Command::EditReplace, "置換(&R)…")
+            .accel("Ctrl-H")
+            .menu(MenuId::Edit)
+            .enabled(is_editable)
+            .handler(|frame, _| frame.show_find_dialog(true)),
+        Spec::new(Command::EditGo, "行へ移動(&G)…")
+            .accel("Ctrl-G")
+            .menu(MenuId::Edit),
+        Spec::new(wx::ID_SELECTALL, "すべて選択(&A)")
+            .accel("Ctrl-A")
+            .menu(MenuId::Edit)
+            .separator()
+            .handler(forward_to_editor),
+        Spec::new(Command::EditDate, "日付と時刻(&D)")
+            .accel("F5")
+            .menu(MenuId::Edit)
+            .enabled(is_editable)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .handle_command(&EditorCommand::Command(Command::EditDate))
+            }),
+        Spec::new(Command::EditUpperCase, "大文字(&U)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .separator()
+            .enabled(can_convert)
+            .handler(|frame, _| frame.editor.transform_selection(str::to_uppercase)),
+        Spec::new(Command::EditLowerCase, "小文字(&L)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .enabled(can_convert)
+            .handler(|frame, _| frame.editor.transform_selection(str::to_lowercase)),
+        Spec::new(Command::EditTitleCase, "単語の先頭を大文字(&T)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_title_case)
+            }),
+        Spec::new(Command::EditFullWidthAlphanumeric, "全角英数字(&F)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .separator()
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_full_width_alphanumeric)
+            }),
+        Spec::new(Command::EditHalfWidthAlphanumeric, "半角英数字(&H)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_half_width_alphanumeric)
+            }),
+        Spec::new(Command::EditFullWidthKatakana, "全角カタカナ(&Z)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .separator()
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_full_width_katakana)
+            }),
+        Spec::new(Command::EditHalfWidthKatakana, "半角カタカナ(&N)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_half_width_katakana)
+            }),
+        Spec::new(Command::EditKatakana, "ひらがなをカタカナ(&K)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .separator()
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_katakana)
+            }),
+        Spec::new(Command::EditHiragana, "カタカナをひらがな(&I)")
+            .menu(MenuId::Edit)
+            .submenu("文字の変換(&O)")
+            .enabled(can_convert)
+            .handler(|frame, _| {
+                frame
+                    .editor
+                    .transform_selection(text_transform::to_hiragana)
+            }),
+        Spec::new(Command::EditDuplicateLine, "行を複製(&C)")
+            .accel("Ctrl-D")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(editor_command(Command::EditDuplicateLine)),
+        Spec::new(Command::EditDeleteLine, "行を削除(&L)")
+            .accel("Ctrl-Shift-K")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(editor_command(Command::EditDeleteLine)),
+        Spec::new(Command::EditMoveLineUp, "行を上に移動(&P)")
+            .accel("Alt-Up")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(editor_command(Command::EditMoveLineUp)),
+        Spec::new(Command::EditMoveLineDown, "行を下に移動(&W)")
+            .accel("Alt-Down")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(editor_command(Command::EditMoveLineDown)),
+        Spec::new(Command::EditJoinLines, "行を連結(&O)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(editor_command(Command::EditJoinLines)),
+        Spec::new(Command::EditSortAscending, "昇順に並べ替え(&A)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .separator()
+            .enabled(is_editable)
+            .handler(|frame, _| frame.sort_lines(false)),
+        Spec::new(Command::EditSortDescending, "降順に並べ替え(&D)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.sort_lines(true)),
+        Spec::new(
+            Command::EditSortIgnoreCase,
+            "大文字と小文字を区別しない(&I)",
+        )
+        .menu(MenuId::Edit)
+        .submenu("行の操作(&J)")
+        .checked(|frame| frame.sort_options.get().ignore_case)
+        .handler(|frame, _| frame.toggle_sort_ignore_case()),
+        Spec::new(Command::EditSortNumeric, "数字を数として比べる(&N)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .checked(|frame| frame.sort_options.get().numeric)
+            .handler(|frame, _| frame.toggle_sort_numeric()),
+        Spec::new(Command::EditRemoveDuplicateLines, "重複した行を削除(&U)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .separator()
+            .enabled(is_editable)
+            .handler(|frame, _| frame.editor.transform_lines(lines::unique)),
+        Spec::new(Command::EditRemoveBlankLines, "空行を削除(&B)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.editor.transform_lines(lines::remove_blank)),
+        Spec::new(Command::EditReverseLines, "逆順に並べ替え(&R)")
+            .menu(MenuId::Edit)
+            .submenu("行の操作(&J)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.editor.transform_lines(lines::reverse)),
+        // 書式
+        Spec::new(Command::FormatWordWrap, "右端で折り返す(&W)")
+            .menu(MenuId::Format)
+            .checked(|frame| frame.editor.word_wrap())
+            .handler(|frame, _| frame.editor.set_word_wrap(!frame.editor.word_wrap())),
+        Spec::new(Command::FormatTabsToSpaces, "行頭のタブを空白に変換(&S)")
+            .menu(MenuId::Format)
+            .submenu("インデント(&I)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.convert_indent(true)),
+        Spec::new(Command::FormatSpacesToTabs, "行頭の空白をタブに変換(&T)")
+            .menu(MenuId::Format)
+            .submenu("インデント(&I)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.convert_indent(false)),
+        Spec::new(Command::FormatTabWidth2, "タブの幅 2(&2)")
+            .menu(MenuId::Format)
+            .submenu("インデント(&I)")
+            .separator()
+            .checked(|frame| frame.editor.tab_width() == 2)
+            .handler(|frame, _| frame.set_tab_width(2)),
+        Spec::new(Command::FormatTabWidth4, "タブの幅 4(&4)")
+            .menu(MenuId::Format)
+            .submenu("インデント(&I)")
+            .checked(|frame| frame.editor.tab_width() == 4)
+            .handler(|frame, _| frame.set_tab_width(4)),
+        Spec::new(Command::FormatTabWidth8, "タブの幅 8(&8)")
+            .menu(MenuId::Format)
+            .submenu("インデント(&I)")
+            .checked(|frame| frame.editor.tab_width() == 8)
+            .handler(|frame, _| frame.set_tab_width(8)),
+        Spec::new(Command::FormatIndentWithSpaces, "空白でインデント(&U)")
+            .menu(MenuId::Format)
+            .submenu("インデント(&I)")
+            .separator()
+            .checked(|frame| frame.editor.indent_with_spaces())
+            .handler(|frame, _| frame.toggle_indent_with_spaces()),
+        Spec::new(
+            Command::FormatTrimTrailingWhitespace,
+            "行末の空白を削除(&T)",
+        )
+        .menu(MenuId::Format)
+        .submenu("空白の整理(&H)")
+        .enabled(is_editable)
+        .handler(|frame, _| frame.clean_up_now(true, FinalNewline::Keep)),
+        Spec::new(Command::FormatEnsureFinalNewline, "末尾に改行を追加(&E)")
+            .menu(MenuId::Format)
+            .submenu("空白の整理(&H)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.clean_up_now(false, FinalNewline::Ensure)),
+        Spec::new(Command::FormatRemoveFinalNewline, "末尾の改行を削除(&R)")
+            .menu(MenuId::Format)
+            .submenu("空白の整理(&H)")
+            .enabled(is_editable)
+            .handler(|frame, _| frame.clean_up_now(false, FinalNewline::Remove)),
+        Spec::new(Command::FormatTrimOnSave, "保存時に行末の空白を削除(&S)")
+            .menu(MenuId::Format)
+            .submenu("空白の整理(&H)")
+            .separator()
+            .checked(|frame| frame.editor.cleanup().trim_trailing_whitespace)
+            .handler(|frame, _| {
+                frame.update_cleanup(|c| c.trim_trailing_whitespace = !c.trim_trailing_whitespace)
+            }),
+        Spec::new(
+            Command::FormatEnsureFinalNewlineOnSave,
+            "保存時に末尾に改行を追加(&N)",
+        )
+        .menu(MenuId::Format)
+        .submenu("空白の整理(&H)")
+        .checked(|frame| frame.editor.cleanup().final_newline == FinalNewline::Ensure)
+        .handler(|frame, _| frame.toggle_final_newline_on_save(FinalNewline::Ensure)),
+        Spec::new(
+            Command::FormatRemoveFinalNewlineOnSave,
+            "保存時に末尾の改行を削除(&M)",
+        )
+        .menu(MenuId::Format)
+        .submenu("空白の整理(&H)")
+        .checked(|frame| frame.editor.cleanup().final_newline == FinalNewline::Remove)
+        .handler(|frame, _| frame.toggle_final_newline_on_save(FinalNewline::Remove)),
+        Spec::new(Command::FormatTrimFullWidthSpace, "全角空白も削除する(&F)")
+            .menu(MenuId::Format)
+            .submenu("空白の整理(&H)")
+            .separator()
+            .checked(|frame| frame.editor.cleanup().trim_full_width_space)
+            .handler(|frame, _| {
+                frame.update_cleanup(|c| c.trim_full_width_space = !c.trim_full_width_space)
+            }),
+        Spec::new(Command::FormatLanguageAuto, "ファイル名から判定(&A)")
+            .menu(MenuId::Format)
+            .submenu("言語(&L)")
+            .enabled(|frame| frame.editor.supports_syntax())
+            .checked(|frame| frame.editor.language_choice() == syntax::Choice::Auto)
+            .handler(|frame, _| frame.set_language_choice(syntax::Choice::Auto)),
+        Spec::new(Command::FormatPlainText, "プレーンテキスト(&P)")
+            .menu(MenuId::Format)
+            .submenu("言語(&L)")
+            .separator()
+            .enabled(|frame| frame.editor.supports_syntax())
+            .checked(|frame| frame.editor.language_choice() == syntax::Choice::PlainText)
+            .handler(|frame, _| frame.set_language_choice(syntax::Choice::PlainText)),
+        Spec::new(Command::FormatFont, "フォント(&O)…").menu(MenuId::Format),
+        // 表示
+        Spec::new(Command::ViewZoomIn, "拡大(&I)")
+            .accel("Ctrl-+")
+            .menu(MenuId::View)
+            .submenu("ズーム(&Z)")
+            .handler(|frame, _| frame.editor.zoom_in()),
+        Spec::new(Command::ViewZoomOut, "縮小(&O)")
+            .accel("Ctrl--")
+            .menu(MenuId::View)
+            .submenu("ズーム(&Z)")
+            .handler(|frame, _| frame.editor.zoom_out()),
+        Spec::new(Command::ViewZoomReset, "既定のズームに戻す(&R)")
+            .accel("Ctrl-0")
+            .menu(MenuId::View)
+            .submenu("ズーム(&Z)")
+            .handler(|frame, _| frame.editor.reset_zoom()),
+        Spec::new(Command::ViewLineNumbers, "行番号(&L)")
+            .menu(MenuId::View)
+            .checked(|frame| frame.editor.line_numbers())
+            .handler(|frame, _| frame.toggle_line_numbers()),
+        Spec::new(Command::ViewStatusBar, "ステータスバー(&S)")
+            .menu(MenuId::View)
+            .checked(|frame| frame.is_status_bar_shown())
+            .handler(|frame, _| frame.toggle_status_bar()),
+        Spec::new(Command::ViewMarkdownPreview, "Markdown のプレビュー(&M)")
+            .menu(MenuId::View)
+            .enabled(|frame| frame.is_markdown())
+            .checked(|frame| frame.preview.is_shown())
+            .handler(|frame, _| frame.toggle_markdown_preview()),
+        Spec::new(Command::ViewHex, "16 進数で表示(&H)…")
+            .menu(MenuId::View)
+            .enabled(|frame| frame.editor.path().is_some())
+            .handler(|frame, _| frame.show_hex()),
+        Spec::new(Command::ViewStatistics, "文書の統計(&T)…")
+            .menu(MenuId::View)
+            .handler(|frame, _| frame.show_statistics()),
+        Spec::new(Command::ViewCommandPalette, "コマンドパレット(&P)…")
+            .accel("Ctrl-Shift-P")
+            .menu(MenuId::View)
+            .separator()
+            .handler(|frame, _| frame.show_command_palette()),
+        // ヘルプ
+        Spec::new(Command::Help, "ヘルプの表示(&H)")
+            .menu(MenuId::Help)
+            .handler(|frame, _| frame.open_help()),
+        Spec::new(wx::ID_ABOUT, "バージョン情報(&A)")
+            .menu(MenuId::Help)
+            .separator()
+            .handler(|frame, _| frame.show_about()),
+    ] {
+        registry.register(spec);
+    }
+    registry
+}
+
+/// `syntax::LANGUAGES` から言語メニューのコマンドを作ります。
+fn language_commands() -> impl Iterator<Item = CommandSpec<EditorFrame>> {
+    syntax::LANGUAGES.iter().map(|language| {
+        CommandSpec::new(CommandRegistry::<EditorFrame>::new_id(), language.label)
+            .menu(MenuId::Format)
+            .submenu("言語(&L)")
+            .enabled(|frame| frame.editor.supports_syntax())
+            .checked(move |frame| frame.editor.language_choice() == syntax::Choice::Fixed(language))
+            .handler(move |frame, _| frame.set_language_choice(syntax::Choice::Fixed(language)))
+    })
+}
+
 impl unsaved_changes::UI for wx::Frame {
     fn confirm_save<CB: FnOnce(Option<bool>)>(&self, on_complete: CB) {
         // TODO: メッセージ調整
@@ -331,11 +1794,30 @@ impl unsaved_changes::UI for wx::Frame {
             None
         });
     }
+    fn confirm_lossy_save(&self) -> bool {
+        let answer = wx::message_box(
+            "この文書には、元の文字コードで保存できない文字か、\
+             読み込んだときに失われたバイトが含まれています。\n\
+             保存するとファイルの内容が変わります。保存しますか？",
+            APP_NAME,
+            wx::YES_NO | (wx::NO_DEFAULT | wx::ICON_WARNING | wx::CENTRE) as c_int,
+            Some(self),
+        );
+        answer == wx::YES
+    }
 }
 impl Observer<DocumentEvent> for EditorFrame {
     fn on_notify(&self, event: DocumentEvent) {
         match event {
-            DocumentEvent::TextModified => self.update_title(),
+            DocumentEvent::TextModified => {
+                self.update_title();
+                self.update_preview();
+                if self.find_bar.is_shown() {
+                    self.refresh_find_bar(false);
+                }
+            }
+            // 表示倍率はホイールで何度も変わるので、ウィンドウを閉じるときに保存します。
+            DocumentEvent::ZoomChanged => self.update_status_bar(),
         }
     }
 }
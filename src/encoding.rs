@@ -0,0 +1,330 @@
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+
+/// BOM がないときに試す文字コード。先にあるものを優先します。
+const CANDIDATES: [&Encoding; 3] = [UTF_8, SHIFT_JIS, EUC_JP];
+
+/// 制御文字がこの割合を超えたらテキストではないとみなします。
+const MAX_CONTROL_RATIO: usize = 100;
+
+/// ファイルの改行コード
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+impl LineEnding {
+    /// `text` の改行コードを返します。CR LF が 1 つでもあれば CR LF とみなします。
+    pub fn detect(text: &str) -> Self {
+        if text.contains("\r\n") {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// CR のない改行をこの改行コードにそろえます。
+    /// Windows の wx::TextCtrl は改行を LF にして返すため、保存前に元に戻します。
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEnding::CrLf if text.matches('\n').count() != text.matches("\r\n").count() => {
+                Cow::Owned(text.replace("\r\n", "\n").replace('\n', "\r\n"))
+            }
+            _ => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// ファイルの文字コード、BOM の有無と改行コード
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextFormat {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+impl Default for TextFormat {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+            line_ending: LineEnding::default(),
+        }
+    }
+}
+impl TextFormat {
+    pub fn name(&self) -> String {
+        if self.bom {
+            format!("{} (BOM 付き)", self.encoding.name())
+        } else {
+            self.encoding.name().to_owned()
+        }
+    }
+}
+
+/// 読み込んだテキスト。`lossy` が真なら、元のバイト列を復元できません。
+pub struct Decoded {
+    pub text: String,
+    pub format: TextFormat,
+    pub lossy: bool,
+}
+
+/// テキストとして解釈できる文字コードを探してデコードします。
+/// どの文字コードでも不正なバイト列や NUL などの制御文字を含むなら、
+/// バイナリファイルとみなして `None` を返します。
+pub fn decode(bytes: &[u8]) -> Option<Decoded> {
+    decode_with_cut(bytes, 0)
+}
+
+/// ファイルの先頭だけを読み込んだ `bytes` をデコードします。
+/// 末尾で途中まで切れた文字は、文字コードを決めてから、その文字コードの区切りで捨てます。
+pub fn decode_prefix(bytes: &[u8]) -> Option<Decoded> {
+    // どの文字コードでも、1 文字は 4 バイト以下です。
+    decode_with_cut(bytes, 3)
+}
+
+/// 文字コードごとに、末尾の `max_cut` バイトまでを捨ててデコードを試します。
+fn decode_with_cut(bytes: &[u8], max_cut: usize) -> Option<Decoded> {
+    let decode_cut = |bytes: &[u8], format: TextFormat| {
+        // すべてを捨てると、どの文字コードでも空のテキストとして読めてしまいます。
+        (0..=max_cut.min(bytes.len().saturating_sub(1)))
+            .find_map(|cut| decode_strict(&bytes[..bytes.len() - cut], format))
+    };
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let format = TextFormat {
+            encoding,
+            bom: true,
+            ..TextFormat::default()
+        };
+        return decode_cut(&bytes[bom_len..], format);
+    }
+    CANDIDATES.iter().find_map(|&encoding| {
+        decode_cut(
+            bytes,
+            TextFormat {
+                encoding,
+                ..TextFormat::default()
+            },
+        )
+    })
+}
+
+fn decode_strict(bytes: &[u8], format: TextFormat) -> Option<Decoded> {
+    let text = format
+        .encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)?;
+    if !looks_like_text(&text) {
+        return None;
+    }
+    Some(Decoded {
+        format: TextFormat {
+            line_ending: LineEnding::detect(&text),
+            ..format
+        },
+        text: text.into_owned(),
+        lossy: false,
+    })
+}
+
+/// バイナリファイルを、不正なバイト列を置き換えながら UTF-8 として読み込みます。
+pub fn decode_lossy(bytes: &[u8]) -> Decoded {
+    let (text, _) = UTF_8.decode_without_bom_handling(bytes);
+    Decoded {
+        format: TextFormat {
+            line_ending: LineEnding::detect(&text),
+            ..TextFormat::default()
+        },
+        // NUL は TextCtrl に渡すとそこで切れてしまうため置き換えます。
+        text: text.replace('\0', "\u{FFFD}"),
+        lossy: true,
+    }
+}
+
+fn looks_like_text(text: &str) -> bool {
+    let mut chars = 0;
+    let mut controls = 0;
+    for c in text.chars() {
+        chars += 1;
+        match c {
+            '\0' => return false,
+            // タブ、改行、改ページ、エスケープシーケンス、DOS の EOF は許します。
+            '\t' | '\n' | '\r' | '\x0b' | '\x0c' | '\x1b' | '\x1a' => (),
+            c if c.is_control() => controls += 1,
+            _ => (),
+        }
+    }
+    controls * MAX_CONTROL_RATIO <= chars
+}
+
+/// テキストを `format` で符号化します。改行は `format` の改行コードにそろえます。
+/// 表現できない文字があれば、2 番目の値が真になります。
+pub fn encode(text: &str, format: TextFormat) -> (Vec<u8>, bool) {
+    let text = format.line_ending.apply(text);
+    let text = text.as_ref();
+    let mut bytes = Vec::new();
+    if format.bom {
+        if format.encoding == UTF_16LE {
+            bytes.extend_from_slice(&[0xFF, 0xFE]);
+        } else if format.encoding == UTF_16BE {
+            bytes.extend_from_slice(&[0xFE, 0xFF]);
+        } else {
+            bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+        }
+    }
+    // encoding_rs は UTF-16 への符号化を扱わないため、自前で変換します。
+    if format.encoding == UTF_16LE {
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        return (bytes, false);
+    }
+    if format.encoding == UTF_16BE {
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        return (bytes, false);
+    }
+    let (encoded, _, had_errors) = format.encoding.encode(text);
+    bytes.extend_from_slice(&encoded);
+    (bytes, had_errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_utf8() {
+        let decoded = decode("日本語\n".as_bytes()).unwrap();
+        assert_eq!(decoded.text, "日本語\n");
+        assert_eq!(decoded.format, TextFormat::default());
+        assert!(!decoded.lossy);
+    }
+
+    #[test]
+    fn decode_with_bom() {
+        let decoded = decode(b"\xEF\xBB\xBFabc").unwrap();
+        assert_eq!(decoded.text, "abc");
+        assert!(decoded.format.bom);
+
+        let decoded = decode(b"\xFF\xFEa\x00\x42\x30").unwrap();
+        assert_eq!(decoded.text, "aあ");
+        assert_eq!(decoded.format.encoding, UTF_16LE);
+    }
+
+    #[test]
+    fn decode_shift_jis() {
+        // Given: UTF-8 としては不正な Shift_JIS の「日本語」
+        let bytes = b"\x93\xfa\x96\x7b\x8c\xea";
+        // When: デコードすると
+        let decoded = decode(bytes).unwrap();
+        // Then: Shift_JIS として読める
+        assert_eq!(decoded.text, "日本語");
+        assert_eq!(decoded.format.encoding, SHIFT_JIS);
+    }
+
+    #[test]
+    fn decode_prefix_drops_cut_character() {
+        // Given: 「日本」の「本」の途中で切った Shift_JIS のバイト列
+        let bytes = b"\x93\xfa\x96";
+        // When: 先頭だけを読み込んだものとしてデコードすると
+        let decoded = decode_prefix(bytes).unwrap();
+        // Then: Shift_JIS と判定し、切れた文字だけを捨てる
+        assert_eq!(decoded.text, "日");
+        assert_eq!(decoded.format.encoding, SHIFT_JIS);
+
+        // UTF-8 と UTF-16 も、それぞれの区切りで捨てる
+        assert_eq!(decode_prefix(&"あい".as_bytes()[..5]).unwrap().text, "あ");
+        let decoded = decode_prefix(b"\xFF\xFEa\x00\x42").unwrap();
+        assert_eq!(decoded.text, "a");
+        assert_eq!(decoded.format.encoding, UTF_16LE);
+        // 切れていなければ何も捨てない
+        assert_eq!(decode_prefix("あい".as_bytes()).unwrap().text, "あい");
+    }
+
+    #[test]
+    fn nul_bytes_are_binary() {
+        assert!(decode(b"MZ\x90\x00\x03\x00\x00\x00").is_none());
+    }
+
+    #[test]
+    fn many_control_characters_are_binary() {
+        assert!(decode(b"\x01\x02\x03\x04abc").is_none());
+        assert!(decode(b"\x1b[31mred\x1b[0m\n").is_some());
+    }
+
+    #[test]
+    fn decode_lossy_replaces_nul() {
+        let decoded = decode_lossy(b"a\x00b");
+        assert_eq!(decoded.text, "a\u{FFFD}b");
+        assert!(decoded.lossy);
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        for format in [
+            TextFormat::default(),
+            TextFormat {
+                encoding: UTF_8,
+                bom: true,
+                ..TextFormat::default()
+            },
+            TextFormat {
+                encoding: UTF_16LE,
+                bom: true,
+                ..TextFormat::default()
+            },
+            TextFormat {
+                encoding: UTF_16BE,
+                bom: true,
+                ..TextFormat::default()
+            },
+            TextFormat {
+                encoding: SHIFT_JIS,
+                ..TextFormat::default()
+            },
+        ] {
+            let (bytes, lossy) = encode("かな\nkana", format);
+            assert!(!lossy);
+            let decoded = decode(&bytes).unwrap();
+            assert_eq!(decoded.text, "かな\nkana");
+            assert_eq!(decoded.format, format);
+        }
+    }
+
+    #[test]
+    fn encode_unrepresentable_is_lossy() {
+        let format = TextFormat {
+            encoding: SHIFT_JIS,
+            ..TextFormat::default()
+        };
+        let (_, lossy) = encode("😀", format);
+        assert!(lossy);
+    }
+
+    #[test]
+    fn crlf_round_trip() {
+        // Given: CR LF のファイルを読み込み
+        let decoded = decode(b"a\r\nb\r\n").unwrap();
+        assert_eq!(decoded.format.line_ending, LineEnding::CrLf);
+        // When: 編集コントロールが改行を LF にして返しても
+        let text = decoded.text.replace("\r\n", "\n");
+        let (bytes, _) = encode(&format!("{}c\n", text), decoded.format);
+        // Then: CR LF で保存する
+        assert_eq!(bytes, b"a\r\nb\r\nc\r\n");
+        // CR LF のままのテキストは二重に変換しない
+        let (bytes, _) = encode(&decoded.text, decoded.format);
+        assert_eq!(bytes, b"a\r\nb\r\n");
+    }
+
+    #[test]
+    fn lf_is_kept() {
+        let decoded = decode(b"a\nb").unwrap();
+        assert_eq!(decoded.format.line_ending, LineEnding::Lf);
+        assert_eq!(encode("a\nb", decoded.format).0, b"a\nb");
+    }
+}
@@ -0,0 +1,128 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+pub const MB: u64 = 1024 * 1024;
+pub const DEFAULT_LARGE_FILE_THRESHOLD_MB: u64 = 50;
+pub const DEFAULT_PREVIEW_MB: u64 = 10;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Cancelled,
+}
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+/// ファイルの先頭から最大 `limit` バイトを読み込みます。
+///
+/// 別スレッドから呼び出せるよう、読み込んだバイト数を `progress` に書き込み、
+/// `cancel` が立ったら `LoadError::Cancelled` を返して中断します。
+pub fn read(
+    path: &Path,
+    limit: Option<u64>,
+    progress: &AtomicU64,
+    cancel: &AtomicBool,
+) -> Result<Vec<u8>, LoadError> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let size = limit.map_or(size, |limit| limit.min(size));
+    let mut bytes = Vec::with_capacity(size as usize);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    while (bytes.len() as u64) < size {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(LoadError::Cancelled);
+        }
+        let want = CHUNK_SIZE.min((size - bytes.len() as u64) as usize);
+        let read = file.read(&mut chunk[..want])?;
+        if read == 0 {
+            // 読み込み中にファイルが短くなった
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..read]);
+        progress.store(bytes.len() as u64, Ordering::Relaxed);
+    }
+    Ok(bytes)
+}
+
+/// ファイルに書き込めるかを調べます。
+/// 属性だけでは所有者や ACL による制限が分からないため、書き込み用に開いてみます。
+pub fn is_writable(path: &Path) -> bool {
+    OpenOptions::new().write(true).open(path).is_ok()
+}
+
+/// ファイルの読み取り専用属性を外します。
+pub fn make_writable(path: &Path) -> io::Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // 所有者にだけ書き込みを許します。
+        permissions.set_mode(permissions.mode() | 0o200);
+    }
+    #[cfg(not(unix))]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("claw_editor_{}_{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn read_whole_file() {
+        let path = temp_file("whole", b"hello");
+        let progress = AtomicU64::new(0);
+        let bytes = read(&path, None, &progress, &AtomicBool::new(false)).unwrap();
+        assert_eq!(bytes, b"hello");
+        assert_eq!(progress.load(Ordering::Relaxed), 5);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_with_limit() {
+        let path = temp_file("limit", b"0123456789");
+        let bytes = read(&path, Some(4), &AtomicU64::new(0), &AtomicBool::new(false)).unwrap();
+        assert_eq!(bytes, b"0123");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_cancelled() {
+        let path = temp_file("cancel", b"0123456789");
+        let result = read(&path, None, &AtomicU64::new(0), &AtomicBool::new(true));
+        assert!(matches!(result, Err(LoadError::Cancelled)));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn make_writable_clears_read_only() {
+        // Given: 読み取り専用のファイルを
+        let path = temp_file("readonly", b"x");
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+        // When: 書き込めるようにすると
+        make_writable(&path).unwrap();
+        // Then: 読み取り専用ではなくなり、書き込める
+        assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(is_writable(&path));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+use std::rc::Rc;
+
+use wx;
+use wx::methods::*;
+
+#[derive(Clone, Copy)]
+pub enum FindBarEvent {
+    /// 検索する文字列が変わった
+    Changed,
+    FindNext,
+    FindPrevious,
+    Close,
+}
+
+/// エディタの下に表示する、入力しながら検索するためのバーです。
+pub struct FindBar {
+    base: wx::Panel,
+    find_text: wx::TextCtrl,
+    status: wx::StaticText,
+}
+impl FindBar {
+    pub fn new<W: WindowMethods, F: Fn(FindBarEvent) + 'static>(
+        parent: &W,
+        on_event: F,
+    ) -> Rc<Self> {
+        let base = wx::Panel::builder(Some(parent)).build();
+        let label = wx::StaticText::builder(Some(&base))
+            .label("検索(&I):")
+            .build();
+        let find_text = wx::TextCtrl::builder(Some(&base))
+            .style(wx::TE_PROCESS_ENTER.into())
+            .build();
+        let next_button = wx::Button::builder(Some(&base))
+            .label("次へ")
+            .style(wx::BU_EXACTFIT.into())
+            .build();
+        let previous_button = wx::Button::builder(Some(&base))
+            .label("前へ")
+            .style(wx::BU_EXACTFIT.into())
+            .build();
+        let status = wx::StaticText::builder(Some(&base)).label("").build();
+        let close_button = wx::Button::builder(Some(&base))
+            .label("×")
+            .style(wx::BU_EXACTFIT.into())
+            .build();
+        close_button.set_tool_tip_str("閉じる (Esc)");
+
+        let sizer = wx::BoxSizer::new(wx::HORIZONTAL);
+        sizer.add_window_int(
+            Some(&label),
+            0,
+            wx::ALIGN_CENTER_VERTICAL | wx::LEFT | wx::RIGHT,
+            4,
+            wx::Object::none(),
+        );
+        sizer.add_window_int(
+            Some(&find_text),
+            1,
+            wx::ALIGN_CENTER_VERTICAL | wx::ALL,
+            2,
+            wx::Object::none(),
+        );
+        for button in [&next_button, &previous_button] {
+            sizer.add_window_int(
+                Some(button),
+                0,
+                wx::ALIGN_CENTER_VERTICAL | wx::ALL,
+                2,
+                wx::Object::none(),
+            );
+        }
+        sizer.add_window_int(
+            Some(&status),
+            1,
+            wx::ALIGN_CENTER_VERTICAL | wx::LEFT | wx::RIGHT,
+            8,
+            wx::Object::none(),
+        );
+        sizer.add_window_int(
+            Some(&close_button),
+            0,
+            wx::ALIGN_CENTER_VERTICAL | wx::ALL,
+            2,
+            wx::Object::none(),
+        );
+        base.set_sizer_and_fit(Some(&sizer), true);
+        base.hide();
+
+        let on_event = Rc::new(on_event);
+        let on_event_copy = on_event.clone();
+        find_text.bind(wx::RustEvent::Text, move |_: &wx::CommandEvent| {
+            on_event_copy(FindBarEvent::Changed);
+        });
+        let on_event_copy = on_event.clone();
+        find_text.bind(wx::RustEvent::KeyDown, move |event: &wx::KeyEvent| {
+            match event.get_key_code() {
+                // Shift + Enter で前へ戻ります。
+                wx::K_RETURN | wx::K_NUMPAD_ENTER if event.shift_down() => {
+                    on_event_copy(FindBarEvent::FindPrevious)
+                }
+                wx::K_RETURN | wx::K_NUMPAD_ENTER => on_event_copy(FindBarEvent::FindNext),
+                wx::K_ESCAPE => on_event_copy(FindBarEvent::Close),
+                _ => event.skip(true),
+            }
+        });
+        for (button, bar_event) in [
+            (&next_button, FindBarEvent::FindNext),
+            (&previous_button, FindBarEvent::FindPrevious),
+            (&close_button, FindBarEvent::Close),
+        ] {
+            let on_event = on_event.clone();
+            button.bind(wx::RustEvent::Button, move |_: &wx::CommandEvent| {
+                on_event(bar_event);
+            });
+        }
+
+        Rc::new(Self {
+            base,
+            find_text,
+            status,
+        })
+    }
+
+    pub fn window(&self) -> &wx::Panel {
+        &self.base
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.base.is_shown()
+    }
+
+    /// バーを表示し、検索する文字列を入力できるようにします。
+    pub fn show(&self, pattern: Option<&str>) {
+        if let Some(pattern) = pattern {
+            self.find_text.change_value(pattern);
+        }
+        self.base.show(true);
+        self.find_text.set_focus();
+        self.find_text.select_all();
+    }
+
+    pub fn hide(&self) {
+        self.base.hide();
+    }
+
+    pub fn pattern(&self) -> String {
+        self.find_text.get_value()
+    }
+
+    /// 「n / m 件」のような検索結果をバーに表示します。
+    pub fn set_status(&self, status: &str) {
+        self.status.set_label(status);
+    }
+}
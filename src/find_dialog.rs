@@ -0,0 +1,260 @@
+use std::rc::Rc;
+
+use wx;
+use wx::methods::*;
+
+use crate::search::{FindQuery, SearchOptions, Searcher};
+
+#[derive(Clone, Copy)]
+pub enum FindAction {
+    FindNext,
+    FindPrevious,
+    Replace,
+    ReplaceAll,
+}
+
+/// 検索と置換で共用する、モードレスのダイアログです。
+pub struct FindDialog {
+    base: wx::Dialog,
+    find_text: wx::TextCtrl,
+    replace_label: wx::StaticText,
+    replace_text: wx::TextCtrl,
+    replace_button: wx::Button,
+    replace_all_button: wx::Button,
+    match_case: wx::CheckBox,
+    regex: wx::CheckBox,
+    multiline: wx::CheckBox,
+    wrap_around: wx::CheckBox,
+    whole_word: wx::CheckBox,
+    in_selection: wx::CheckBox,
+    error: wx::StaticText,
+}
+impl FindDialog {
+    pub fn new<W: WindowMethods, F: Fn(FindAction) + 'static>(
+        parent: &W,
+        on_action: F,
+    ) -> Rc<Self> {
+        let base = wx::Dialog::builder(Some(parent))
+            .title("検索")
+            .style((wx::DEFAULT_DIALOG_STYLE | wx::RESIZE_BORDER).into())
+            .build();
+        let find_label = wx::StaticText::builder(Some(&base))
+            .label("検索する文字列(&N):")
+            .build();
+        let find_text = wx::TextCtrl::builder(Some(&base))
+            .style(wx::TE_PROCESS_ENTER.into())
+            .build();
+        let replace_label = wx::StaticText::builder(Some(&base))
+            .label("置換後の文字列(&P):")
+            .build();
+        let replace_text = wx::TextCtrl::builder(Some(&base)).build();
+        let match_case = wx::CheckBox::builder(Some(&base))
+            .label("大文字と小文字を区別する(&C)")
+            .build();
+        let regex = wx::CheckBox::builder(Some(&base))
+            .label("正規表現(&E)")
+            .build();
+        let multiline = wx::CheckBox::builder(Some(&base))
+            .label("^ と $ を各行に一致させる(&M)")
+            .build();
+        let wrap_around = wx::CheckBox::builder(Some(&base))
+            .label("折り返す(&R)")
+            .build();
+        let whole_word = wx::CheckBox::builder(Some(&base))
+            .label("単語単位で探す(&W)")
+            .build();
+        let in_selection = wx::CheckBox::builder(Some(&base))
+            .label("選択範囲内(&S)")
+            .build();
+        let error = wx::StaticText::builder(Some(&base)).label("").build();
+        error.set_foreground_colour(&wx::Colour::new_with_str("RED"));
+
+        let find_next_button = wx::Button::builder(Some(&base))
+            .label("次を検索(&F)")
+            .build();
+        let find_previous_button = wx::Button::builder(Some(&base))
+            .label("前を検索(&B)")
+            .build();
+        let replace_button = wx::Button::builder(Some(&base))
+            .label("置換して次へ(&R)")
+            .build();
+        let replace_all_button = wx::Button::builder(Some(&base))
+            .label("すべて置換(&A)")
+            .build();
+        let cancel_button = wx::Button::builder(Some(&base))
+            .id(wx::ID_CANCEL)
+            .label("キャンセル")
+            .build();
+
+        let fields = wx::FlexGridSizer::new_with_int(2, 4, 4);
+        fields.add_growable_col(1, 1);
+        fields.add_window_int(
+            Some(&find_label),
+            0,
+            wx::ALIGN_CENTER_VERTICAL,
+            0,
+            wx::Object::none(),
+        );
+        fields.add_window_int(Some(&find_text), 1, wx::EXPAND, 0, wx::Object::none());
+        fields.add_window_int(
+            Some(&replace_label),
+            0,
+            wx::ALIGN_CENTER_VERTICAL,
+            0,
+            wx::Object::none(),
+        );
+        fields.add_window_int(Some(&replace_text), 1, wx::EXPAND, 0, wx::Object::none());
+
+        let left = wx::BoxSizer::new(wx::VERTICAL);
+        left.add_sizer_int(
+            Some(&fields),
+            0,
+            wx::EXPAND | wx::BOTTOM,
+            8,
+            wx::Object::none(),
+        );
+        for check_box in [
+            &match_case,
+            &whole_word,
+            &regex,
+            &multiline,
+            &wrap_around,
+            &in_selection,
+        ] {
+            left.add_window_int(Some(check_box), 0, wx::BOTTOM, 4, wx::Object::none());
+        }
+        left.add_window_int(Some(&error), 0, wx::EXPAND, 0, wx::Object::none());
+
+        let buttons = wx::BoxSizer::new(wx::VERTICAL);
+        for button in [
+            &find_next_button,
+            &find_previous_button,
+            &replace_button,
+            &replace_all_button,
+            &cancel_button,
+        ] {
+            buttons.add_window_int(
+                Some(button),
+                0,
+                wx::EXPAND | wx::BOTTOM,
+                4,
+                wx::Object::none(),
+            );
+        }
+
+        let sizer = wx::BoxSizer::new(wx::HORIZONTAL);
+        sizer.add_sizer_int(Some(&left), 1, wx::EXPAND | wx::ALL, 8, wx::Object::none());
+        sizer.add_sizer_int(Some(&buttons), 0, wx::ALL, 8, wx::Object::none());
+        base.set_sizer_and_fit(Some(&sizer), true);
+
+        let on_action = Rc::new(on_action);
+        for (button, action) in [
+            (&find_next_button, FindAction::FindNext),
+            (&find_previous_button, FindAction::FindPrevious),
+            (&replace_button, FindAction::Replace),
+            (&replace_all_button, FindAction::ReplaceAll),
+        ] {
+            let on_action = on_action.clone();
+            button.bind(wx::RustEvent::Button, move |_: &wx::CommandEvent| {
+                on_action(action);
+            });
+        }
+        let on_action_copy = on_action.clone();
+        find_text.bind(wx::RustEvent::TextEnter, move |_: &wx::CommandEvent| {
+            on_action_copy(FindAction::FindNext);
+        });
+        let base_copy = base.clone();
+        cancel_button.bind(wx::RustEvent::Button, move |_: &wx::CommandEvent| {
+            base_copy.hide();
+        });
+
+        let dialog = Rc::new(Self {
+            base,
+            find_text,
+            replace_label,
+            replace_text,
+            replace_button,
+            replace_all_button,
+            match_case,
+            regex,
+            multiline,
+            wrap_around,
+            whole_word,
+            in_selection,
+            error,
+        });
+        let weak_dialog = Rc::downgrade(&dialog);
+        dialog
+            .regex
+            .bind(wx::RustEvent::CheckBox, move |_: &wx::CommandEvent| {
+                if let Some(dialog) = weak_dialog.upgrade() {
+                    dialog.update_controls();
+                }
+            });
+        // 入力中の正規表現の誤りをすぐに知らせます。
+        let weak_dialog = Rc::downgrade(&dialog);
+        dialog
+            .find_text
+            .bind(wx::RustEvent::Text, move |_: &wx::CommandEvent| {
+                if let Some(dialog) = weak_dialog.upgrade() {
+                    dialog.update_controls();
+                }
+            });
+        dialog
+    }
+
+    /// 検索モードか置換モードでダイアログを表示します。
+    /// 選択範囲がなければ「選択範囲内」は選べません。
+    pub fn show(&self, replace: bool, query: &FindQuery, has_selection: bool) {
+        self.base.set_title(if replace { "置換" } else { "検索" });
+        self.find_text.set_value(&query.pattern);
+        self.replace_text.set_value(&query.replacement);
+        let options = &query.options;
+        self.match_case.set_value(options.match_case);
+        self.regex.set_value(options.regex);
+        self.multiline.set_value(options.multiline);
+        self.wrap_around.set_value(options.wrap_around);
+        self.whole_word.set_value(options.whole_word);
+        self.in_selection
+            .set_value(has_selection && options.in_selection);
+        self.in_selection.enable(has_selection);
+        self.replace_label.show(replace);
+        self.replace_text.show(replace);
+        self.replace_button.show(replace);
+        self.replace_all_button.show(replace);
+        self.update_controls();
+        self.base.show(true);
+        self.base.raise();
+        self.find_text.set_focus();
+        self.find_text.select_all();
+    }
+
+    pub fn query(&self) -> FindQuery {
+        FindQuery {
+            pattern: self.find_text.get_value(),
+            replacement: self.replace_text.get_value(),
+            options: SearchOptions {
+                match_case: self.match_case.get_value(),
+                regex: self.regex.get_value(),
+                multiline: self.multiline.get_value(),
+                wrap_around: self.wrap_around.get_value(),
+                whole_word: self.whole_word.get_value(),
+                in_selection: self.in_selection.is_enabled() && self.in_selection.get_value(),
+            },
+        }
+    }
+
+    /// 正規表現の誤りなどをダイアログの中に表示します。
+    pub fn set_error(&self, message: Option<&str>) {
+        self.error.set_label(message.unwrap_or(""));
+        self.base.layout();
+    }
+
+    fn update_controls(&self) {
+        self.multiline.enable(self.regex.get_value());
+        match Searcher::new(&self.query()) {
+            Ok(_) => self.set_error(None),
+            Err(e) => self.set_error(Some(&e.to_string())),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// パターンの文字が順番通りに含まれていれば一致とみなす、
+// コマンドパレット向けのあいまい検索です。
+
+use std::cmp::Reverse;
+
+const MATCH: i32 = 1;
+const CONSECUTIVE_BONUS: i32 = 5;
+const WORD_START_BONUS: i32 = 3;
+
+/// `pattern` が `text` にあいまい一致すればスコアを返します。
+/// スコアが大きいほど良い一致です。
+pub fn score(pattern: &str, text: &str) -> Option<i32> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut prev_matched = false;
+    let mut prev_char: Option<char> = None;
+    for c in text.chars() {
+        if next == pattern.len() {
+            break;
+        }
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        if lower == pattern[next] {
+            score += MATCH;
+            if prev_matched {
+                score += CONSECUTIVE_BONUS;
+            }
+            if is_word_start(prev_char, c) {
+                score += WORD_START_BONUS;
+            }
+            next += 1;
+            prev_matched = true;
+        } else {
+            prev_matched = false;
+        }
+        prev_char = Some(c);
+    }
+    if next == pattern.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+fn is_word_start(prev: Option<char>, c: char) -> bool {
+    match prev {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()),
+    }
+}
+
+/// `items` のうち `pattern` に一致するもののインデックスを、スコアの高い順に返します。
+/// 同点の場合は元の順序を保ちます。
+pub fn filter<S: AsRef<str>>(pattern: &str, items: &[S]) -> Vec<usize> {
+    let mut matches: Vec<(usize, i32)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| score(pattern, item.as_ref()).map(|score| (i, score)))
+        .collect();
+    matches.sort_by_key(|&(_, score)| Reverse(score));
+    matches.into_iter().map(|(i, _)| i).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_pattern_matches_everything() {
+        assert_eq!(score("", "保存(&S)"), Some(0));
+        assert_eq!(filter("", &["a", "b"]), vec![0, 1]);
+    }
+
+    #[test]
+    fn subsequence_matches() {
+        assert!(score("ns", "新規 New Save").is_some());
+        assert!(score("新保", "新規 保存").is_some());
+        assert!(score("sn", "New Save").is_none());
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert!(score("CTRL", "ctrl-n").is_some());
+        assert!(score("ctrl", "Ctrl-N").is_some());
+    }
+
+    #[test]
+    fn consecutive_match_ranks_higher() {
+        // Given: 連続して一致するものと飛び飛びに一致するもの
+        let items = ["f-i-n-d", "find"];
+        // When: 絞り込むと
+        let result = filter("find", &items);
+        // Then: 連続して一致する方が先に来る
+        assert_eq!(result, vec![1, 0]);
+    }
+
+    #[test]
+    fn unmatched_items_are_removed() {
+        let items = ["検索", "置換", "次を検索"];
+        assert_eq!(filter("検索", &items), vec![0, 2]);
+    }
+}
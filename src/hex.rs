@@ -0,0 +1,424 @@
+use std::cell::{Cell, RefCell};
+use std::fmt::Write;
+use std::os::raw::c_long;
+use std::rc::Rc;
+
+use wx;
+use wx::methods::*;
+
+pub const BYTES_PER_LINE: usize = 16;
+/// 16 進数で編集できるファイルの大きさ (MB)
+pub const MAX_MB: u64 = 16;
+
+// `dump()` の 1 行の文字数。オフセットと空白、16 進数の列と中央の空白、
+// 空白 2 つ、ASCII の列と改行です。
+const LINE_WIDTH: usize = 9 + BYTES_PER_LINE * 3 + 1 + 2 + BYTES_PER_LINE + 1;
+// ASCII の列が始まる位置
+const ASCII_COLUMN: usize = LINE_WIDTH - BYTES_PER_LINE - 1;
+
+/// バイト列をオフセット、16 進数、ASCII の 3 列に整形します。
+pub fn dump(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    for (i, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        write_line(&mut result, i * BYTES_PER_LINE, line);
+    }
+    result
+}
+
+/// `dump()` の `offset` のバイトを含む 1 行を返します。
+fn dump_line(bytes: &[u8], offset: usize) -> String {
+    let start = offset - offset % BYTES_PER_LINE;
+    let end = (start + BYTES_PER_LINE).min(bytes.len());
+    let mut result = String::new();
+    write_line(&mut result, start, &bytes[start..end]);
+    result
+}
+
+fn write_line(result: &mut String, offset: usize, line: &[u8]) {
+    _ = write!(result, "{:08X} ", offset);
+    for column in 0..BYTES_PER_LINE {
+        if column == BYTES_PER_LINE / 2 {
+            result.push(' ');
+        }
+        match line.get(column) {
+            Some(b) => _ = write!(result, " {:02X}", b),
+            None => result.push_str("   "),
+        }
+    }
+    result.push_str("  ");
+    result.extend(line.iter().map(|&b| printable(b)));
+    result.push('\n');
+}
+
+// 行の中で、`column` 列目のバイトの 16 進数が始まる位置
+fn hex_column(column: usize) -> usize {
+    let gap = if column >= BYTES_PER_LINE / 2 { 1 } else { 0 };
+    10 + column * 3 + gap
+}
+
+/// 16 進数の桁 (`nibble` 番目。バイトごとに上位、下位の順) の、`dump()` の中での位置
+fn position_of(nibble: usize) -> usize {
+    let offset = nibble / 2;
+    offset / BYTES_PER_LINE * LINE_WIDTH + hex_column(offset % BYTES_PER_LINE) + nibble % 2
+}
+
+/// `dump()` の中の位置にある 16 進数の桁を返します。
+/// 16 進数の前の空白や ASCII の列なら、そのバイトの上位の桁を返します。
+fn nibble_at(position: usize, len: usize) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    let line = position / LINE_WIDTH;
+    let x = position % LINE_WIDTH;
+    let (column, low) = if x >= ASCII_COLUMN {
+        ((x - ASCII_COLUMN).min(BYTES_PER_LINE - 1), 0)
+    } else {
+        let column = (0..BYTES_PER_LINE)
+            .rev()
+            .find(|&column| hex_column(column) <= x + 1)
+            .unwrap_or(0);
+        let low = if x == hex_column(column) + 1 { 1 } else { 0 };
+        (column, low)
+    };
+    let offset = line * BYTES_PER_LINE + column;
+    if offset >= len {
+        return Some((len - 1) * 2);
+    }
+    Some(offset * 2 + low)
+}
+
+/// 16 進数のオフセットを読み取ります。先頭の "0x" は省略できます。
+pub fn parse_offset(text: &str) -> Option<usize> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    usize::from_str_radix(digits, 16).ok()
+}
+
+/// `byte` の上位 (`low` が偽) または下位の桁を `digit` で上書きします。
+fn set_nibble(byte: u8, low: bool, digit: u8) -> u8 {
+    if low {
+        (byte & 0xF0) | digit
+    } else {
+        (digit << 4) | (byte & 0x0F)
+    }
+}
+
+pub fn printable(b: u8) -> char {
+    if b.is_ascii_graphic() || b == b' ' {
+        b as char
+    } else {
+        '.'
+    }
+}
+
+/// バイト列を 16 進数で表示し、上書きで編集するダイアログです。
+struct HexEditor {
+    view: wx::TextCtrl,
+    status: wx::StaticText,
+    bytes: RefCell<Vec<u8>>,
+    // カーソルのある 16 進数の桁
+    cursor: Cell<usize>,
+    editable: bool,
+    modified: Cell<bool>,
+}
+impl HexEditor {
+    fn len(&self) -> usize {
+        self.bytes.borrow().len()
+    }
+
+    fn move_to(&self, nibble: usize) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        let nibble = nibble.min(len * 2 - 1);
+        self.cursor.set(nibble);
+        let position = position_of(nibble) as c_long;
+        self.view.set_selection(position, position + 1);
+        self.view.show_position(position);
+        let offset = nibble / 2;
+        self.status.set_label(&format!(
+            "オフセット {:08X} ({})  値 {:02X}  大きさ {} バイト",
+            offset,
+            offset,
+            self.bytes.borrow()[offset],
+            len
+        ));
+    }
+
+    /// カーソルを `delta` 桁動かします。
+    fn move_by(&self, delta: isize) {
+        let nibble = self.cursor.get() as isize + delta;
+        self.move_to(nibble.max(0) as usize);
+    }
+
+    /// カーソルの桁を `digit` で上書きし、次の桁に進みます。
+    fn overwrite(&self, digit: u8) {
+        if !self.editable || self.len() == 0 {
+            wx::bell();
+            return;
+        }
+        let nibble = self.cursor.get();
+        let offset = nibble / 2;
+        let line = {
+            let mut bytes = self.bytes.borrow_mut();
+            bytes[offset] = set_nibble(bytes[offset], nibble % 2 == 1, digit);
+            dump_line(&bytes, offset)
+        };
+        self.modified.set(true);
+        // 書き換えた行だけを表示し直します。行の長さは変わりません。
+        let from = (offset / BYTES_PER_LINE * LINE_WIDTH) as c_long;
+        // 読み取り専用のままでは書き換えられないプラットフォームがあります。
+        self.view.set_editable(true);
+        self.view.replace(from, from + line.len() as c_long, &line);
+        self.view.set_editable(false);
+        self.move_to(nibble + 1);
+    }
+
+    fn on_key_down(&self, event: &wx::KeyEvent) {
+        let line = BYTES_PER_LINE as isize * 2;
+        let nibble = self.cursor.get();
+        match event.get_key_code() {
+            wx::K_LEFT => self.move_by(-1),
+            wx::K_RIGHT => self.move_by(1),
+            wx::K_UP => self.move_by(-line),
+            wx::K_DOWN => self.move_by(line),
+            wx::K_PAGEUP => self.move_by(-line * 16),
+            wx::K_PAGEDOWN => self.move_by(line * 16),
+            wx::K_HOME if event.control_down() => self.move_to(0),
+            wx::K_END if event.control_down() => self.move_to(usize::MAX),
+            wx::K_HOME => self.move_to(nibble - nibble % line as usize),
+            wx::K_END => self.move_to(nibble - nibble % line as usize + line as usize - 1),
+            code => {
+                let digit = char::from_u32(code as u32)
+                    .filter(|_| !event.control_down() && !event.alt_down())
+                    .and_then(|c| c.to_digit(16));
+                match digit {
+                    Some(digit) => self.overwrite(digit as u8),
+                    None => event.skip(true),
+                }
+            }
+        }
+    }
+}
+
+/// `path` の内容 `bytes` を 16 進数で表示します。
+/// `editable` なら上書きで編集でき、保存を選ぶと編集したバイト列を返します。
+pub fn edit<W: WindowMethods>(
+    parent: &W,
+    path: &str,
+    bytes: Vec<u8>,
+    editable: bool,
+) -> Option<Vec<u8>> {
+    let dialog = wx::Dialog::builder(Some(parent))
+        .title(&format!("16 進数 - {}", path))
+        .style((wx::DEFAULT_DIALOG_STYLE | wx::RESIZE_BORDER).into())
+        .build();
+    let offset_label = wx::StaticText::builder(Some(&dialog))
+        .label("オフセット(&O):")
+        .build();
+    let offset = wx::TextCtrl::builder(Some(&dialog))
+        .style(wx::TE_PROCESS_ENTER.into())
+        .build();
+    let go_button = wx::Button::builder(Some(&dialog)).label("移動(&G)").build();
+    let view = wx::TextCtrl::builder(Some(&dialog))
+        .value(&dump(&bytes))
+        .style((wx::TE_MULTILINE | wx::TE_READONLY | wx::TE_DONTWRAP | wx::TE_RICH2).into())
+        .size(wx::Size::new_with_int(640, 400))
+        .build();
+    // 列をそろえるため、等幅のフォントで表示します。
+    let font = view.get_font();
+    font.set_family(wx::FONTFAMILY_TELETYPE);
+    view.set_font(&font);
+    let status = wx::StaticText::builder(Some(&dialog)).label("").build();
+
+    let row = wx::BoxSizer::new(wx::HORIZONTAL);
+    row.add_window_int(
+        Some(&offset_label),
+        0,
+        wx::ALIGN_CENTER_VERTICAL | wx::RIGHT,
+        4,
+        wx::Object::none(),
+    );
+    row.add_window_int(Some(&offset), 1, wx::RIGHT, 4, wx::Object::none());
+    row.add_window_int(Some(&go_button), 0, 0, 0, wx::Object::none());
+    let buttons = wx::BoxSizer::new(wx::HORIZONTAL);
+    buttons.add_stretch_spacer(1);
+    if editable {
+        let save_button = wx::Button::builder(Some(&dialog))
+            .id(wx::ID_OK)
+            .label("保存(&S)")
+            .build();
+        buttons.add_window_int(Some(&save_button), 0, wx::RIGHT, 4, wx::Object::none());
+    }
+    let cancel_button = wx::Button::builder(Some(&dialog))
+        .id(wx::ID_CANCEL)
+        .label(if editable {
+            "キャンセル"
+        } else {
+            "閉じる"
+        })
+        .build();
+    buttons.add_window_int(Some(&cancel_button), 0, 0, 0, wx::Object::none());
+
+    let sizer = wx::BoxSizer::new(wx::VERTICAL);
+    sizer.add_sizer_int(Some(&row), 0, wx::EXPAND | wx::ALL, 8, wx::Object::none());
+    sizer.add_window_int(
+        Some(&view),
+        1,
+        wx::EXPAND | wx::LEFT | wx::RIGHT,
+        8,
+        wx::Object::none(),
+    );
+    sizer.add_window_int(
+        Some(&status),
+        0,
+        wx::EXPAND | wx::ALL,
+        8,
+        wx::Object::none(),
+    );
+    sizer.add_sizer_int(
+        Some(&buttons),
+        0,
+        wx::EXPAND | wx::LEFT | wx::RIGHT | wx::BOTTOM,
+        8,
+        wx::Object::none(),
+    );
+    dialog.set_sizer_and_fit(Some(&sizer), true);
+
+    let editor = Rc::new(HexEditor {
+        view: view.clone(),
+        status,
+        bytes: RefCell::new(bytes),
+        cursor: Cell::new(0),
+        editable,
+        modified: Cell::new(false),
+    });
+    let editor_copy = editor.clone();
+    view.bind(wx::RustEvent::KeyDown, move |event: &wx::KeyEvent| {
+        editor_copy.on_key_down(event);
+    });
+    let editor_copy = editor.clone();
+    view.bind(wx::RustEvent::LeftUp, move |event: &wx::MouseEvent| {
+        // クリックした位置の桁にカーソルを移します。
+        let position = editor_copy.view.get_insertion_point();
+        if let Some(nibble) = nibble_at(position.max(0) as usize, editor_copy.len()) {
+            editor_copy.move_to(nibble);
+        }
+        event.skip(true);
+    });
+    let jump = {
+        let editor = editor.clone();
+        let offset = offset.clone();
+        move || match parse_offset(&offset.get_value()) {
+            Some(target) if target < editor.len() => {
+                editor.move_to(target * 2);
+                editor.view.set_focus();
+            }
+            _ => wx::bell(),
+        }
+    };
+    let jump_copy = jump.clone();
+    offset.bind(wx::RustEvent::TextEnter, move |_: &wx::CommandEvent| {
+        jump_copy();
+    });
+    go_button.bind(wx::RustEvent::Button, move |_: &wx::CommandEvent| {
+        jump();
+    });
+
+    editor.move_to(0);
+    view.set_focus();
+    let result = dialog.show_modal();
+    dialog.destroy();
+    if result != wx::ID_OK || !editor.modified.get() {
+        return None;
+    }
+    let bytes = editor.bytes.borrow().clone();
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dump_full_line() {
+        let bytes: Vec<u8> = (0x40..0x50).collect();
+        assert_eq!(
+            dump(&bytes),
+            "00000000  40 41 42 43 44 45 46 47  48 49 4A 4B 4C 4D 4E 4F  @ABCDEFGHIJKLMNO\n"
+        );
+    }
+
+    #[test]
+    fn dump_partial_line() {
+        // 足りない列は空白で埋め、ASCII 列の位置をそろえます。
+        let padding = " ".repeat(3 * 13 + 1);
+        assert_eq!(
+            dump(b"A\x00\n"),
+            format!("00000000  41 00 0A{}  A..\n", padding)
+        );
+    }
+
+    #[test]
+    fn dump_empty() {
+        assert_eq!(dump(b""), "");
+    }
+    #[test]
+    fn dump_line_matches_dump() {
+        let bytes: Vec<u8> = (0..40).collect();
+        let text = dump(&bytes);
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        assert_eq!(dump_line(&bytes, 17), lines[1]);
+        assert_eq!(dump_line(&bytes, 39), lines[2]);
+        assert_eq!(lines[0].len(), LINE_WIDTH);
+    }
+
+    #[test]
+    fn nibble_positions_round_trip() {
+        // Given: 3 行にわたるダンプで
+        let bytes: Vec<u8> = (0..40).collect();
+        let text = dump(&bytes);
+        // When/Then: 各桁の位置にはその桁の文字があり、位置から桁に戻せる
+        for nibble in 0..bytes.len() * 2 {
+            let position = position_of(nibble);
+            let expected = format!("{:02X}", bytes[nibble / 2]);
+            assert_eq!(text[position..=position], expected[nibble % 2..=nibble % 2]);
+            assert_eq!(nibble_at(position, bytes.len()), Some(nibble));
+        }
+    }
+
+    #[test]
+    fn nibble_at_between_columns() {
+        let text = dump(b"ABCDEFGHIJKLMNOPQ");
+        // 16 進数の前の空白はそのバイトの上位の桁
+        assert_eq!(nibble_at(text.find(" 42").unwrap(), 17), Some(2));
+        // ASCII の列はそのバイトの上位の桁
+        assert_eq!(nibble_at(ASCII_COLUMN + 3, 17), Some(6));
+        // 最後の行の空いているところは最後のバイト
+        assert_eq!(nibble_at(LINE_WIDTH + 30, 17), Some(32));
+        assert_eq!(nibble_at(0, 0), None);
+    }
+
+    #[test]
+    fn parse_hex_offsets() {
+        assert_eq!(parse_offset("1F"), Some(0x1F));
+        assert_eq!(parse_offset(" 0x0100 "), Some(0x100));
+        assert_eq!(parse_offset("ff"), Some(0xFF));
+        assert_eq!(parse_offset(""), None);
+        assert_eq!(parse_offset("+1"), None);
+        assert_eq!(parse_offset("0xG"), None);
+    }
+
+    #[test]
+    fn overwrite_nibbles() {
+        assert_eq!(set_nibble(0x12, false, 0xA), 0xA2);
+        assert_eq!(set_nibble(0x12, true, 0xA), 0x1A);
+    }
+}
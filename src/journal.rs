@@ -0,0 +1,62 @@
+// メモ帳と同じく、先頭の行が「.LOG」のファイルを開くたびに末尾へ日時を追記します。
+
+use wx;
+use wx::methods::*;
+
+const LOG_MARKER: &str = ".LOG";
+
+/// 「日付と時刻」で挿入する文字列を返します。メモ帳と同じく時刻、日付の順です。
+pub fn timestamp(date: &str, time: &str) -> String {
+    format!("{} {}", time, date)
+}
+
+/// 現在の日時を「日付と時刻」の形式で返します。
+pub fn now() -> String {
+    let now = wx::DateTime::now();
+    timestamp(&now.format_date(), &now.format_time())
+}
+
+/// 先頭の行がちょうど「.LOG」なら真を返します。
+pub fn is_log(text: &str) -> bool {
+    let first_line = text.lines().next().unwrap_or("");
+    first_line == LOG_MARKER
+}
+
+/// `text` の末尾に追記する、改行と日時の行を返します。
+pub fn entry(text: &str, timestamp: &str) -> String {
+    let separator = if text.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}\n", separator, timestamp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamp_puts_time_first() {
+        assert_eq!(timestamp("2022/05/01", "12:34"), "12:34 2022/05/01");
+    }
+
+    #[test]
+    fn log_marker_must_be_whole_first_line() {
+        assert!(is_log(".LOG"));
+        assert!(is_log(".LOG\nメモ"));
+        assert!(is_log(".LOG\r\nメモ"));
+        // メモ帳と同じく大文字だけを認めます。
+        assert!(!is_log(".log\n"));
+        assert!(!is_log(" .LOG\n"));
+        assert!(!is_log(".LOG 作業記録\n"));
+        assert!(!is_log("メモ\n.LOG\n"));
+        assert!(!is_log(""));
+    }
+
+    #[test]
+    fn entry_starts_on_new_line() {
+        // Given: 末尾に改行がないログに
+        // When: 追記すると
+        // Then: 改行してから日時を書く
+        assert_eq!(entry(".LOG", "12:34 2022/05/01"), "\n12:34 2022/05/01\n");
+        // 改行で終わっていれば、そのまま日時を書く
+        assert_eq!(entry(".LOG\n", "12:34 2022/05/01"), "12:34 2022/05/01\n");
+    }
+}
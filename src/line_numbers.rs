@@ -0,0 +1,253 @@
+// wx::TextCtrl の左に行番号を表示する余白です。
+// wx::StyledTextCtrl は Scintilla の余白に表示するので、これは使いません。
+
+use std::cell::{Cell, RefCell};
+use std::os::raw::{c_int, c_long, c_void};
+use std::rc::Rc;
+
+use wx::methods::*;
+
+use crate::position::{self, Unit};
+
+// 行番号が少なくても確保する桁数
+const MIN_DIGITS: usize = 2;
+// 行番号の左右の余白
+const PADDING: c_int = 6;
+
+/// `line_count` 行の行番号を表示するのに必要な桁数を返します。
+pub fn digits(line_count: usize) -> usize {
+    line_count.max(1).to_string().len().max(MIN_DIGITS)
+}
+
+/// 各行の先頭の位置を `unit` で数えて返します。
+pub fn line_starts(text: &str, unit: Unit) -> Vec<c_long> {
+    let mut starts = vec![0];
+    // 先頭から変換し直さないよう、前の行からの差分で位置を求めます。
+    let mut last = 0;
+    let mut pos = 0;
+    for (i, _) in text.match_indices('\n') {
+        pos += position::from_byte(&text[last..], i + 1 - last, unit);
+        last = i + 1;
+        starts.push(pos);
+    }
+    starts
+}
+
+/// 位置 `pos` を含む行の番号 (0 から数える) を返します。
+pub fn line_at(starts: &[c_long], pos: c_long) -> usize {
+    starts
+        .partition_point(|&start| start <= pos)
+        .saturating_sub(1)
+}
+
+pub struct LineNumberGutter {
+    panel: wx::Panel,
+    ctrl: wx::TextCtrl,
+    // 行番号と編集コントロールを横に並べるサイザー
+    row: wx::BoxSizer,
+    // 各行の先頭の位置。テキストが変わったら `None` にし、次に使うときに数え直します。
+    line_starts: RefCell<Option<Vec<c_long>>>,
+    digits: Cell<usize>,
+    // 前回描いたときの一番上の位置、文字数、高さ。変わったときだけ描き直します。
+    last_view: Cell<(c_long, c_long, c_int)>,
+}
+impl LineNumberGutter {
+    pub fn new<W: WindowMethods>(parent: &W, ctrl: &wx::TextCtrl) -> Rc<Self> {
+        let panel = wx::Panel::builder(Some(parent)).build();
+        panel.set_background_colour(&wx::SystemSettings::get_colour(wx::SYS_COLOUR_BTNFACE));
+        panel.show(false);
+        let row = wx::BoxSizer::new(wx::HORIZONTAL);
+        row.add_window_int(Some(&panel), 0, wx::EXPAND, 0, wx::Object::none());
+        row.add_window_int(Some(ctrl), 1, wx::EXPAND, 0, wx::Object::none());
+        let gutter = Rc::new(Self {
+            panel,
+            ctrl: ctrl.clone(),
+            row,
+            line_starts: RefCell::new(None),
+            digits: Cell::new(0),
+            last_view: Cell::new((-1, -1, -1)),
+        });
+        let gutter_copy = gutter.clone();
+        gutter
+            .panel
+            .bind(wx::RustEvent::Paint, move |_: &wx::PaintEvent| {
+                gutter_copy.paint();
+            });
+        let gutter_copy = gutter.clone();
+        gutter
+            .panel
+            .bind(wx::RustEvent::LeftDown, move |event: &wx::MouseEvent| {
+                gutter_copy.select_line_at(event.get_y());
+            });
+        let gutter_copy = gutter.clone();
+        ctrl.bind(wx::RustEvent::Text, move |event: &wx::CommandEvent| {
+            gutter_copy.text_changed();
+            event.skip(true);
+        });
+        // wx::TextCtrl はスクロールを通知しないため、アイドル時の更新で位置の変化を調べます。
+        let gutter_copy = gutter.clone();
+        ctrl.bind(wx::RustEvent::UpdateUI, move |event: &wx::UpdateUIEvent| {
+            gutter_copy.refresh_if_moved();
+            event.skip(true);
+        });
+        gutter
+    }
+
+    /// 行番号と編集コントロールを `sizer` に追加します。
+    pub fn add_to_sizer(&self, sizer: &wx::BoxSizer, proportion: c_int, flag: c_int) {
+        sizer.add_sizer_int(Some(&self.row), proportion, flag, 0, wx::Object::none());
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.panel.is_shown()
+    }
+
+    pub fn show(&self, visible: bool) {
+        self.panel.show(visible);
+        self.refresh();
+    }
+
+    /// テキストが変わったときに、行の位置を数え直すようにします。
+    /// `change_value()` は wx::RustEvent::Text を送らないので、そのときは呼び出し側から呼びます。
+    pub fn text_changed(&self) {
+        self.line_starts.take();
+    }
+
+    /// 各行の先頭の位置を返します。テキストが変わっていれば数え直します。
+    fn with_line_starts<R>(&self, f: impl FnOnce(&[c_long]) -> R) -> R {
+        let mut cache = self.line_starts.borrow_mut();
+        let starts =
+            cache.get_or_insert_with(|| line_starts(&self.ctrl.get_value(), position::NATIVE));
+        f(starts)
+    }
+
+    /// 編集コントロールのフォントが変わったときに、幅を測り直します。
+    pub fn font_changed(&self) {
+        self.digits.set(0);
+        self.refresh();
+    }
+
+    /// 行数に合わせて幅を変え、描き直します。
+    pub fn refresh(&self) {
+        if !self.is_shown() {
+            self.row.layout();
+            return;
+        }
+        let digits = digits(self.with_line_starts(|starts| starts.len()));
+        if digits != self.digits.replace(digits) {
+            self.panel.set_font(&self.ctrl.get_font());
+            let width = self.panel.get_text_extent(&"9".repeat(digits)).get_width();
+            self.panel
+                .set_min_size(&wx::Size::new_with_int(width + PADDING * 2, -1));
+        }
+        self.row.layout();
+        self.last_view.set((-1, -1, -1));
+        self.panel.refresh(true, None);
+    }
+
+    fn refresh_if_moved(&self) {
+        if !self.is_shown() {
+            return;
+        }
+        let view = (
+            self.first_visible_position(),
+            self.ctrl.get_last_position(),
+            self.ctrl.get_client_size().get_height(),
+        );
+        if view == self.last_view.get() {
+            return;
+        }
+        if view.1 != self.last_view.get().1 {
+            // 文字数が変わったら、桁数も変わったかもしれません。
+            self.refresh();
+        } else {
+            self.panel.refresh(true, None);
+        }
+        self.last_view.set(view);
+    }
+
+    fn position_at(&self, x: c_int, y: c_int) -> c_long {
+        let mut pos: c_long = 0;
+        self.ctrl.hit_test(
+            &wx::Point::new_with_int(x, y),
+            &mut pos as *mut c_long as *mut c_void,
+        );
+        pos
+    }
+
+    fn first_visible_position(&self) -> c_long {
+        self.position_at(0, 0)
+    }
+
+    fn paint(&self) {
+        let dc = wx::PaintDC::new(Some(&self.panel));
+        dc.clear();
+        dc.set_font(&self.ctrl.get_font());
+        dc.set_text_foreground(&wx::SystemSettings::get_colour(wx::SYS_COLOUR_GRAYTEXT));
+        let first = self.first_visible_position();
+        let width = self.panel.get_client_size().get_width();
+        let height = self.ctrl.get_client_size().get_height();
+        self.with_line_starts(|starts| {
+            for (i, &pos) in starts.iter().enumerate().skip(line_at(starts, first)) {
+                // 折り返した行の先頭が上に隠れていると、位置を求められません。
+                let y = self.ctrl.position_to_coords(pos).get_y();
+                if y < 0 {
+                    continue;
+                }
+                if y > height {
+                    break;
+                }
+                let label = (i + 1).to_string();
+                let x = width - PADDING - dc.get_text_extent(&label).get_width();
+                dc.draw_text(&label, x, y);
+            }
+        });
+    }
+
+    /// クリックした行を、行末の改行まで選択します。
+    fn select_line_at(&self, y: c_int) {
+        let pos = self.position_at(0, y);
+        let (start, end) = self.with_line_starts(|starts| {
+            let line = line_at(starts, pos);
+            let end = starts
+                .get(line + 1)
+                .copied()
+                .unwrap_or_else(|| self.ctrl.get_last_position());
+            (starts[line], end)
+        });
+        self.ctrl.set_selection(start, end);
+        self.ctrl.set_focus();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn digits_grow_with_line_count() {
+        assert_eq!(digits(0), 2);
+        assert_eq!(digits(99), 2);
+        assert_eq!(digits(100), 3);
+        assert_eq!(digits(12345), 5);
+    }
+
+    #[test]
+    fn line_starts_in_native_units() {
+        // Given: 2 行目に全角文字と絵文字がある文書で
+        let text = "a\nあ😀\n\nd";
+        // When/Then: 各行の先頭を UTF-16 で数える
+        let starts = line_starts(text, Unit::Utf16);
+        assert_eq!(starts, vec![0, 2, 6, 7]);
+        assert_eq!(line_starts("", Unit::Char), vec![0]);
+    }
+
+    #[test]
+    fn line_at_finds_containing_line() {
+        let starts = [0, 2, 6, 7];
+        assert_eq!(line_at(&starts, 0), 0);
+        assert_eq!(line_at(&starts, 3), 1);
+        assert_eq!(line_at(&starts, 6), 2);
+        assert_eq!(line_at(&starts, 100), 3);
+    }
+}
@@ -0,0 +1,612 @@
+// 行の並べ替えや重複の削除など、行単位でテキストを書き換える処理です。
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::encoding::LineEnding;
+use crate::statistics::is_full_width;
+use crate::text_transform::line_range;
+
+/// 行の複製や移動で、テキストの `range` を `replacement` に置き換え、
+/// 置き換えた後のテキストで `selection` を選択します。位置は UTF-8 のバイト位置です。
+#[derive(PartialEq, Eq, Debug)]
+pub struct LineEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+    pub selection: Range<usize>,
+}
+
+/// 並べ替えの方法
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SortOptions {
+    pub descending: bool,
+    pub ignore_case: bool,
+    /// 行の中の数字を、文字ではなく数として比べます (「2」は「10」より前)。
+    pub numeric: bool,
+}
+
+/// `text` を改行を除いた行に分けて `f` で書き換え、元の末尾の改行を付け直します。
+/// 行は文書の改行コードでつなぐので、CRLF の文書で最後の行を移しても改行がそろいます。
+fn map_lines<F: FnOnce(Vec<&str>) -> Vec<&str>>(text: &str, f: F) -> String {
+    let newline = LineEnding::detect(text).as_str();
+    let (body, final_newline) = match text.strip_suffix('\n') {
+        Some(body) => (body, newline),
+        None => (text, ""),
+    };
+    let lines = body
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    format!("{}{}", f(lines).join(newline), final_newline)
+}
+
+pub fn sort(text: &str, options: SortOptions) -> String {
+    map_lines(text, |mut lines| {
+        // 同じ順位の行は元の順序を保ちます。
+        lines.sort_by(|a, b| {
+            let ordering = compare(a, b, options);
+            if options.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        lines
+    })
+}
+
+fn compare(a: &str, b: &str, options: SortOptions) -> Ordering {
+    if options.ignore_case {
+        let a = a.to_lowercase();
+        let b = b.to_lowercase();
+        compare_case_sensitive(&a, &b, options.numeric)
+    } else {
+        compare_case_sensitive(a, b, options.numeric)
+    }
+}
+
+fn compare_case_sensitive(a: &str, b: &str, numeric: bool) -> Ordering {
+    if !numeric {
+        return a.cmp(b);
+    }
+    let mut a = a;
+    let mut b = b;
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, rest_a) = split_number(a);
+                let (y, rest_b) = split_number(b);
+                let ordering = compare_number(x, y);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = rest_a;
+                b = rest_b;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
+
+/// 先頭の数字の並びと残りに分けます。
+fn split_number(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// 数字の並びを、桁数に制限なく数として比べます。値が同じなら先頭の 0 が少ない方が前です。
+fn compare_number(a: &str, b: &str) -> Ordering {
+    let a_trimmed = a.trim_start_matches('0');
+    let b_trimmed = b.trim_start_matches('0');
+    a_trimmed
+        .len()
+        .cmp(&b_trimmed.len())
+        .then_with(|| a_trimmed.cmp(b_trimmed))
+        .then_with(|| a.len().cmp(&b.len()))
+}
+
+/// 重複した行を削除し、最初に現れた行だけを残します。
+pub fn unique(text: &str) -> String {
+    map_lines(text, |lines| {
+        let mut seen = HashSet::new();
+        lines
+            .into_iter()
+            .filter(|line| seen.insert(*line))
+            .collect()
+    })
+}
+
+/// 空白だけの行も含めて、空行を削除します。
+pub fn remove_blank(text: &str) -> String {
+    map_lines(text, |lines| {
+        lines
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    })
+}
+
+pub fn reverse(text: &str) -> String {
+    map_lines(text, |mut lines| {
+        lines.reverse();
+        lines
+    })
+}
+
+/// 行の `\r` を分けます。CRLF の文書で改行のない最後の行を移動しても、改行の種類を保つためです。
+fn split_cr(line: &str) -> (&str, &str) {
+    match line.strip_suffix('\r') {
+        Some(line) => (line, "\r"),
+        None => (line, ""),
+    }
+}
+
+/// 選択範囲を、移動した行の中の同じ位置に移します。
+fn shift_selection(
+    selection: &Range<usize>,
+    old_start: usize,
+    new_start: usize,
+    len: usize,
+) -> Range<usize> {
+    let offset = |pos: usize| new_start + (pos - old_start).min(len);
+    offset(selection.start)..offset(selection.end)
+}
+
+/// キャレットのある行、または選択範囲の行を下に複製し、複製した方を選択します。
+pub fn duplicate(text: &str, selection: Range<usize>) -> LineEdit {
+    let lines = line_range(text, selection.clone());
+    let (block, cr) = split_cr(&text[lines.clone()]);
+    // 同じ行を上に入れ、元の行を複製した方として選択します。
+    // 改行のない最後の行を複製するときも、文書の改行コードで区切ります。
+    let newline = if cr.is_empty() {
+        LineEnding::detect(text).as_str()
+    } else {
+        "\r\n"
+    };
+    let replacement = format!("{}{}", block, newline);
+    let shift = replacement.len();
+    LineEdit {
+        range: lines.start..lines.start,
+        replacement,
+        selection: selection.start + shift..selection.end + shift,
+    }
+}
+
+/// キャレットのある行、または選択範囲の行を削除し、キャレットを次の行の先頭に置きます。
+pub fn delete(text: &str, selection: Range<usize>) -> LineEdit {
+    let lines = line_range(text, selection);
+    if lines.end < text.len() {
+        LineEdit {
+            range: lines.start..lines.end + 1,
+            replacement: String::new(),
+            selection: lines.start..lines.start,
+        }
+    } else {
+        // 最後の行は、前の行の改行と一緒に削除します。
+        let start = lines.start.saturating_sub(1);
+        let caret = line_range(text, start..start).start;
+        LineEdit {
+            range: start..lines.end,
+            replacement: String::new(),
+            selection: caret..caret,
+        }
+    }
+}
+
+/// キャレットのある行、または選択範囲の行を 1 行上に移動します。先頭の行なら `None` です。
+pub fn move_up(text: &str, selection: Range<usize>) -> Option<LineEdit> {
+    let lines = line_range(text, selection.clone());
+    if lines.start == 0 {
+        return None;
+    }
+    let previous = line_range(text, lines.start - 1..lines.start - 1);
+    let (block, block_cr) = split_cr(&text[lines.clone()]);
+    let (previous_text, previous_cr) = split_cr(&text[previous.clone()]);
+    let len = block.len() + previous_cr.len();
+    Some(LineEdit {
+        range: previous.start..lines.end,
+        replacement: format!("{}{}\n{}{}", block, previous_cr, previous_text, block_cr),
+        selection: shift_selection(&selection, lines.start, previous.start, len),
+    })
+}
+
+/// キャレットのある行、または選択範囲の行を 1 行下に移動します。最後の行なら `None` です。
+pub fn move_down(text: &str, selection: Range<usize>) -> Option<LineEdit> {
+    let lines = line_range(text, selection.clone());
+    if lines.end == text.len() {
+        return None;
+    }
+    let next = line_range(text, lines.end + 1..lines.end + 1);
+    let (block, block_cr) = split_cr(&text[lines.clone()]);
+    let (next_text, next_cr) = split_cr(&text[next.clone()]);
+    let new_start = lines.start + next_text.len() + block_cr.len() + 1;
+    let len = block.len() + next_cr.len();
+    Some(LineEdit {
+        range: lines.start..next.end,
+        replacement: format!("{}{}\n{}{}", next_text, block_cr, block, next_cr),
+        selection: shift_selection(&selection, lines.start, new_start, len),
+    })
+}
+
+/// 選択範囲の行を 1 行につなげます。選択範囲が 1 行だけなら次の行をつなげます。
+/// つなぎ目の空白は 1 つにまとめ、全角の文字どうしは空白を入れずにつなげます。
+pub fn join(text: &str, selection: Range<usize>) -> Option<LineEdit> {
+    let mut lines = line_range(text, selection.clone());
+    if !text[lines.clone()].contains('\n') {
+        if lines.end == text.len() {
+            return None;
+        }
+        lines.end = line_range(text, lines.end + 1..lines.end + 1).end;
+    }
+    let block = &text[lines.clone()];
+    let last = block.matches('\n').count();
+    let mut joined = String::new();
+    let mut first_join = None;
+    for (i, line) in block.split('\n').enumerate() {
+        let line = if i == 0 { line } else { line.trim_start() };
+        // 最後の行の `\r` は、つなげた行の改行として残します。
+        let line = if i == last { line } else { line.trim_end() };
+        let separator = match (joined.chars().last(), line.chars().next()) {
+            (Some(a), Some(b)) if !is_full_width(a) && !is_full_width(b) => " ",
+            _ => "",
+        };
+        if i > 0 && first_join.is_none() {
+            first_join = Some(joined.len());
+        }
+        joined.push_str(separator);
+        joined.push_str(line);
+    }
+    let selection = if selection.is_empty() {
+        let caret = lines.start + first_join.unwrap_or(0);
+        caret..caret
+    } else {
+        lines.start..lines.start + joined.len()
+    };
+    Some(LineEdit {
+        range: lines,
+        replacement: joined,
+        selection,
+    })
+}
+
+/// 選択範囲の行の先頭に `unit` を入れ、行全体を選択します。空行には入れません。
+pub fn indent(text: &str, selection: Range<usize>, unit: &str) -> LineEdit {
+    let lines = line_range(text, selection);
+    let replacement = text[lines.clone()]
+        .split('\n')
+        .map(|line| {
+            if split_cr(line).0.is_empty() {
+                line.to_owned()
+            } else {
+                format!("{}{}", unit, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    LineEdit {
+        selection: lines.start..lines.start + replacement.len(),
+        range: lines,
+        replacement,
+    }
+}
+
+/// 行の先頭から、タブ 1 つか `tab_width` 個までの空白を取り除きます。
+fn strip_indent(line: &str, tab_width: usize) -> &str {
+    if let Some(rest) = line.strip_prefix('\t') {
+        return rest;
+    }
+    let spaces = line
+        .bytes()
+        .take(tab_width)
+        .take_while(|&b| b == b' ')
+        .count();
+    &line[spaces..]
+}
+
+/// キャレットのある行、または選択範囲の行のインデントを 1 段減らします。
+/// 選択していなければキャレットを行の同じ文字の前に残し、選択していれば行全体を選択します。
+pub fn outdent(text: &str, selection: Range<usize>, tab_width: usize) -> Option<LineEdit> {
+    let lines = line_range(text, selection.clone());
+    let replacement = text[lines.clone()]
+        .split('\n')
+        .map(|line| strip_indent(line, tab_width.max(1)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let removed = lines.len() - replacement.len();
+    if removed == 0 {
+        return None;
+    }
+    let selection = if selection.is_empty() {
+        let caret = selection.start.saturating_sub(removed).max(lines.start);
+        caret..caret
+    } else {
+        lines.start..lines.start + replacement.len()
+    };
+    Some(LineEdit {
+        range: lines,
+        replacement,
+        selection,
+    })
+}
+
+/// 空白でインデントするとき、`caret` から次のタブ位置までを埋める空白を返します。
+pub fn soft_tab(text: &str, caret: usize, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    let line_start = text[..caret].rfind('\n').map_or(0, |i| i + 1);
+    let column = text[line_start..caret].chars().fold(0, |column, c| {
+        if c == '\t' {
+            (column / tab_width + 1) * tab_width
+        } else {
+            column + 1
+        }
+    });
+    " ".repeat(tab_width - column % tab_width)
+}
+
+/// Enter で改行したときに新しい行に入れる、前の行の先頭の空白とタブを返します。
+/// キャレットがインデントの途中にあれば、キャレットまでの分だけを返します。
+fn auto_indent(text: &str, caret: usize) -> &str {
+    let line_start = text[..caret].rfind('\n').map_or(0, |i| i + 1);
+    let before_caret = &text[line_start..caret];
+    let end = before_caret
+        .find(|c| c != ' ' && c != '\t')
+        .unwrap_or(before_caret.len());
+    &before_caret[..end]
+}
+
+/// Enter で `selection` を改行に置き換えます。改行は文書の改行コードに合わせ、
+/// 新しい行には前の行のインデントを入れます。
+pub fn new_line(text: &str, selection: Range<usize>) -> LineEdit {
+    let replacement = format!(
+        "{}{}",
+        LineEnding::detect(text).as_str(),
+        auto_indent(text, selection.start)
+    );
+    let caret = selection.start + replacement.len();
+    LineEdit {
+        range: selection,
+        replacement,
+        selection: caret..caret,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sort_keeps_final_newline() {
+        // Given: 改行で終わる文書を
+        let text = "b\na\nc\n";
+        // When: 並べ替えると
+        // Then: 最後の空行を先頭に持ってこない
+        assert_eq!(sort(text, SortOptions::default()), "a\nb\nc\n");
+        assert_eq!(
+            sort(
+                "b\r\na\r\nc\r\n",
+                SortOptions {
+                    descending: true,
+                    ..Default::default()
+                }
+            ),
+            "c\r\nb\r\na\r\n"
+        );
+    }
+
+    #[test]
+    fn sort_crlf_without_final_newline() {
+        // Given: 改行で終わらない CRLF の文書を
+        let text = "b\r\na";
+        // When: 並べ替えて最後の行が先頭に来ても
+        // Then: 行は CRLF でつなぐ
+        assert_eq!(sort(text, SortOptions::default()), "a\r\nb");
+        assert_eq!(reverse("1\r\n2\r\n3"), "3\r\n2\r\n1");
+    }
+
+    #[test]
+    fn sort_ignoring_case() {
+        let text = "b\nB\na\nA";
+        assert_eq!(sort(text, SortOptions::default()), "A\nB\na\nb");
+        // 大文字と小文字を区別しなければ、同じ行は元の順序のまま
+        let options = SortOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert_eq!(sort(text, options), "a\nA\nb\nB");
+    }
+
+    #[test]
+    fn sort_numbers_by_value() {
+        let text = "第10章\n第2章\n第1章\n第02章";
+        assert_eq!(
+            sort(text, SortOptions::default()),
+            "第02章\n第10章\n第1章\n第2章"
+        );
+        let options = SortOptions {
+            numeric: true,
+            ..Default::default()
+        };
+        assert_eq!(sort(text, options), "第1章\n第2章\n第02章\n第10章");
+        // 桁の多い数もあふれずに比べる
+        assert_eq!(
+            sort("100000000000000000000\n99999999999999999999", options),
+            "99999999999999999999\n100000000000000000000"
+        );
+    }
+
+    #[test]
+    fn unique_keeps_first_occurrence() {
+        assert_eq!(unique("a\nb\na\nc\nb\n"), "a\nb\nc\n");
+        assert_eq!(unique("a\r\nb\r\na\r\n"), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn remove_blank_lines() {
+        assert_eq!(remove_blank("a\n\n  \n\u{3000}\nb\n"), "a\nb\n");
+    }
+
+    #[test]
+    fn reverse_lines() {
+        assert_eq!(reverse("1\n2\n3\n"), "3\n2\n1\n");
+        assert_eq!(reverse("1\n2"), "2\n1");
+    }
+
+    fn apply(text: &str, edit: &LineEdit) -> String {
+        format!(
+            "{}{}{}",
+            &text[..edit.range.start],
+            edit.replacement,
+            &text[edit.range.end..]
+        )
+    }
+
+    #[test]
+    fn duplicate_selects_copy() {
+        // Given: 2 行目の途中にキャレットがあるとき
+        let text = "a\nbc\nd";
+        // When: 複製すると
+        let edit = duplicate(text, 4..4);
+        // Then: 下に同じ行を入れ、キャレットも複製した行の同じ位置に移す
+        assert_eq!(apply(text, &edit), "a\nbc\nbc\nd");
+        assert_eq!(edit.selection, 7..7);
+        // 複数行の選択
+        let edit = duplicate("a\r\nb\r\n", 0..4);
+        assert_eq!(apply("a\r\nb\r\n", &edit), "a\r\nb\r\na\r\nb\r\n");
+        assert_eq!(edit.selection, 6..10);
+        // CRLF の文書の、改行のない最後の行
+        let edit = duplicate("a\r\nb", 3..3);
+        assert_eq!(apply("a\r\nb", &edit), "a\r\nb\r\nb");
+        assert_eq!(edit.selection, 6..6);
+    }
+
+    #[test]
+    fn delete_lines() {
+        let text = "a\nb\nc";
+        let edit = delete(text, 2..2);
+        assert_eq!(apply(text, &edit), "a\nc");
+        assert_eq!(edit.selection, 2..2);
+        // 最後の行は前の改行ごと削除し、前の行の先頭に置く
+        let edit = delete(text, 4..4);
+        assert_eq!(apply(text, &edit), "a\nb");
+        assert_eq!(edit.selection, 2..2);
+        assert_eq!(apply("a", &delete("a", 0..1)), "");
+    }
+
+    #[test]
+    fn move_lines_up_and_down() {
+        // Given: 2 行目と 3 行目を選択しているとき
+        let text = "1\n22\n33\n4";
+        let selection = 2..7;
+        // When: 上に移動すると
+        let edit = move_up(text, selection.clone()).unwrap();
+        // Then: 選択範囲も一緒に移動する
+        let moved = apply(text, &edit);
+        assert_eq!(moved, "22\n33\n1\n4");
+        assert_eq!(&moved[edit.selection.clone()], &text[selection.clone()]);
+        // When: 下に移動すると
+        let edit = move_down(text, selection.clone()).unwrap();
+        let moved = apply(text, &edit);
+        assert_eq!(moved, "1\n4\n22\n33");
+        assert_eq!(&moved[edit.selection.clone()], &text[selection]);
+        // 端の行はそれ以上移動しない
+        assert_eq!(move_up(text, 0..0), None);
+        assert_eq!(move_down(text, text.len()..text.len()), None);
+    }
+
+    #[test]
+    fn move_keeps_crlf() {
+        // 改行のない最後の行を上に移動しても、CRLF の改行を保つ
+        let text = "a\r\nb";
+        let edit = move_up(text, 3..3).unwrap();
+        assert_eq!(apply(text, &edit), "b\r\na");
+        assert_eq!(edit.selection, 0..0);
+        let edit = move_down(text, 0..0).unwrap();
+        assert_eq!(apply(text, &edit), "b\r\na");
+        assert_eq!(edit.selection, 3..3);
+    }
+
+    #[test]
+    fn join_lines() {
+        // 選択していなければ次の行をつなげ、つなぎ目にキャレットを置く
+        let text = "foo  \n   bar\nbaz";
+        let edit = join(text, 0..0).unwrap();
+        assert_eq!(apply(text, &edit), "foo bar\nbaz");
+        assert_eq!(edit.selection, 3..3);
+        // 選択範囲の行をつなげ、つなげた行を選択する
+        let edit = join(text, 0..text.len()).unwrap();
+        assert_eq!(apply(text, &edit), "foo bar baz");
+        assert_eq!(edit.selection, 0..11);
+        // 日本語の文は空白を入れずにつなげる
+        let text = "日本語の\n文章です。";
+        assert_eq!(
+            apply(text, &join(text, 0..0).unwrap()),
+            "日本語の文章です。"
+        );
+        assert_eq!(join("最後の行", 0..0), None);
+    }
+
+    #[test]
+    fn indent_selected_lines() {
+        // Given: 3 行にまたがる選択範囲を
+        let text = "a\n\n  b\nc";
+        // When: タブでインデントすると
+        let edit = indent(text, 1..6, "\t");
+        // Then: 空行以外の先頭にタブを入れ、行全体を選択する
+        let indented = apply(text, &edit);
+        assert_eq!(indented, "\ta\n\n\t  b\nc");
+        assert_eq!(&indented[edit.selection], "\ta\n\n\t  b");
+    }
+
+    #[test]
+    fn outdent_removes_one_level() {
+        let text = "\ta\n      b\n  c\nd";
+        let edit = outdent(text, 0..text.len(), 4).unwrap();
+        assert_eq!(apply(text, &edit), "a\n  b\nc\nd");
+        // 選択していなければキャレットの行だけを減らし、キャレットを同じ文字の前に置く
+        let edit = outdent(text, 9..9, 4).unwrap();
+        assert_eq!(apply(text, &edit), "\ta\n  b\n  c\nd");
+        assert_eq!(edit.selection, 5..5);
+        // インデントがなければ何もしない
+        assert_eq!(outdent(text, text.len()..text.len(), 4), None);
+    }
+
+    #[test]
+    fn soft_tab_fills_to_next_stop() {
+        assert_eq!(soft_tab("ab", 2, 4), "  ");
+        assert_eq!(soft_tab("x\n\tab", 5, 4), "  ");
+        assert_eq!(soft_tab("abcd", 4, 4), "    ");
+    }
+
+    #[test]
+    fn auto_indent_copies_leading_whitespace() {
+        let text = "fn f() {\n\t  x;";
+        assert_eq!(auto_indent(text, text.len()), "\t  ");
+        // インデントの途中で改行すると、キャレットまでの分だけ
+        assert_eq!(auto_indent(text, 10), "\t");
+        assert_eq!(auto_indent("abc", 3), "");
+    }
+
+    #[test]
+    fn new_line_follows_crlf() {
+        // Given: CRLF の文書のインデントした行で
+        let text = "a\r\n  b";
+        // When: 行末で改行すると
+        let edit = new_line(text, text.len()..text.len());
+        // Then: CRLF とインデントを入れ、その後ろにキャレットを置く
+        assert_eq!(edit.replacement, "\r\n  ");
+        assert_eq!(edit.selection, text.len() + 4..text.len() + 4);
+        assert_eq!(new_line("a\nb", 1..3).replacement, "\n");
+    }
+}
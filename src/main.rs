@@ -4,30 +4,81 @@ use std::path::Path;
 
 use wx;
 
+mod command_palette;
 mod commands;
 
 mod editor_ctrl;
 mod editor_frame;
 use editor_frame::EditorFrame;
 
+mod encoding;
+mod file_loader;
+mod find_bar;
+mod find_dialog;
+mod fuzzy;
+mod hex;
+mod journal;
+mod line_numbers;
+mod lines;
+mod markdown;
+mod markdown_preview;
 mod observer;
+mod position;
+mod printing;
+mod search;
+
+mod session;
+use session::Session;
+
+mod settings;
+mod statistics;
+mod syntax;
+mod text_transform;
+mod text_view;
 mod unsaved_changes;
 
 fn main() {
     wx::App::run(|_| {
-        let frame = EditorFrame::new();
+        let mut read_only = false;
         let mut file_to_open = None;
-        if let Some(file) = wx::App::args().nth(1) {
+        for arg in wx::App::args().skip(1) {
+            if arg == "--readonly" {
+                read_only = true;
+            } else if file_to_open.is_none() {
+                file_to_open = Some(arg);
+            }
+        }
+        if let Some(file) = file_to_open {
+            let frame = EditorFrame::new();
             if !Path::new(&file).exists() {
                 println!("The file {} does not exist.", file);
                 frame.close();
                 return;
             }
-            file_to_open = Some(file);
+            frame.show();
+            frame.open_file(Some(&file));
+            if read_only {
+                frame.set_read_only(true);
+            }
+            return;
+        }
+        // ファイルを指定されなければ、前回終了したときの状態を復元します。
+        let session = Session::load();
+        if session.windows.is_empty() {
+            let frame = EditorFrame::new();
+            frame.show();
+            if read_only {
+                frame.set_read_only(true);
+            }
+            return;
         }
-        frame.show();
-        if file_to_open.is_some() {
-            frame.open_file(file_to_open.as_deref());
+        for state in &session.windows {
+            let frame = EditorFrame::new();
+            frame.show();
+            frame.restore(state);
+            if read_only {
+                frame.set_read_only(true);
+            }
         }
     });
 }
@@ -0,0 +1,708 @@
+// Markdown を wx::HtmlWindow で表示する HTML に変換します。
+// wx::HtmlWindow は HTML の一部しか表示できないので、よく使う記法だけを扱います。
+
+use std::fmt::Write;
+
+/// 変換した HTML と、アンカーを置いた元の行
+pub struct Rendered {
+    pub html: String,
+    /// 最上位のブロックごとに `<a name="…">` を置いた行番号 (0 から数える)。昇順です。
+    pub lines: Vec<usize>,
+}
+
+/// `line` 行目のブロックに置いたアンカーの名前
+pub fn anchor(line: usize) -> String {
+    format!("L{}", line)
+}
+
+/// `text` を HTML に変換します。画像のパスは `resolve_image` で変換します。
+pub fn to_html(text: &str, resolve_image: &dyn Fn(&str) -> String) -> Rendered {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut renderer = Renderer {
+        html: String::new(),
+        lines: Vec::new(),
+        tight: false,
+        resolve_image,
+    };
+    renderer.blocks(&lines, Some(0));
+    Rendered {
+        html: renderer.html,
+        lines: renderer.lines,
+    }
+}
+
+struct Renderer<'a> {
+    html: String,
+    lines: Vec<usize>,
+    // 空行を挟まないリストの中では、段落を <p> で囲みません。
+    tight: bool,
+    resolve_image: &'a dyn Fn(&str) -> String,
+}
+impl Renderer<'_> {
+    /// `first_line` があれば、ブロックごとに元の行番号のアンカーを置きます。
+    fn blocks(&mut self, lines: &[&str], first_line: Option<usize>) {
+        let mut i = 0;
+        while i < lines.len() {
+            if is_blank(lines[i]) {
+                i += 1;
+                continue;
+            }
+            if let Some(first_line) = first_line {
+                self.lines.push(first_line + i);
+                _ = write!(self.html, "<a name=\"{}\"></a>", anchor(first_line + i));
+            }
+            i = self.block(lines, i);
+        }
+    }
+
+    /// `i` 行目から始まるブロックを変換し、次のブロックの行を返します。
+    fn block(&mut self, lines: &[&str], i: usize) -> usize {
+        let line = lines[i];
+        if let Some(fence) = fence(line) {
+            return self.fenced_code(lines, i, fence);
+        }
+        if let Some((level, text)) = heading(line) {
+            let text = self.inline(text);
+            _ = writeln!(self.html, "<h{0}>{1}</h{0}>", level, text);
+            return i + 1;
+        }
+        if is_rule(line) {
+            self.html.push_str("<hr>\n");
+            return i + 1;
+        }
+        if quote(line).is_some() {
+            return self.quote(lines, i);
+        }
+        if let Some(item) = list_item(line) {
+            return self.list(lines, i, item.ordered);
+        }
+        if indented(line).is_some() {
+            return self.indented_code(lines, i);
+        }
+        if i + 1 < lines.len() && line.contains('|') {
+            if let Some(alignments) = table_alignments(lines[i + 1]) {
+                return self.table(lines, i, &alignments);
+            }
+        }
+        self.paragraph(lines, i)
+    }
+
+    fn fenced_code(&mut self, lines: &[&str], i: usize, fence: &str) -> usize {
+        let mut end = i + 1;
+        while end < lines.len() && !lines[end].trim().starts_with(fence) {
+            end += 1;
+        }
+        self.code(&lines[i + 1..end]);
+        // 閉じていなければ文書の最後までをコードにします。
+        (end + 1).min(lines.len())
+    }
+
+    fn indented_code(&mut self, lines: &[&str], i: usize) -> usize {
+        let mut end = i;
+        let mut code = Vec::new();
+        while end < lines.len() {
+            match indented(lines[end]) {
+                Some(line) => code.push(line),
+                None if is_blank(lines[end]) => code.push(""),
+                None => break,
+            }
+            end += 1;
+        }
+        while code.last() == Some(&"") {
+            code.pop();
+        }
+        self.code(&code);
+        end
+    }
+
+    fn code(&mut self, lines: &[&str]) {
+        self.html.push_str("<pre><code>");
+        for line in lines {
+            self.html.push_str(&escape(line));
+            self.html.push('\n');
+        }
+        self.html.push_str("</code></pre>\n");
+    }
+
+    fn quote(&mut self, lines: &[&str], i: usize) -> usize {
+        let mut end = i;
+        let mut inner = Vec::new();
+        while let Some(line) = lines.get(end).and_then(|line| quote(line)) {
+            inner.push(line);
+            end += 1;
+        }
+        self.html.push_str("<blockquote>\n");
+        let tight = std::mem::replace(&mut self.tight, false);
+        self.blocks(&inner, None);
+        self.tight = tight;
+        self.html.push_str("</blockquote>\n");
+        end
+    }
+
+    fn list(&mut self, lines: &[&str], i: usize, ordered: bool) -> usize {
+        // 項目ごとに、行頭の記号と字下げを取り除いた行を集めます。
+        let mut items: Vec<Vec<&str>> = Vec::new();
+        let mut loose = false;
+        let mut start = None;
+        let mut end = i;
+        while let Some(item) = lines.get(end).and_then(|line| list_item(line)) {
+            if item.ordered != ordered {
+                break;
+            }
+            start = start.or(item.number);
+            let mut content = vec![item.text];
+            end += 1;
+            while end < lines.len() {
+                let line = lines[end];
+                if is_blank(line) {
+                    // 空行の後も字下げが続けば、同じ項目の続きです。
+                    let next = lines[end..].iter().position(|line| !is_blank(line));
+                    match next.map(|n| lines[end + n]) {
+                        Some(next) if leading_spaces(next) >= item.indent => {
+                            loose = true;
+                            content.push("");
+                        }
+                        Some(next) if list_item(next).map(|next| next.ordered) == Some(ordered) => {
+                            loose = true;
+                            break;
+                        }
+                        _ => break,
+                    }
+                } else if leading_spaces(line) >= item.indent {
+                    content.push(&line[item.indent..]);
+                } else if list_item(line).is_some() || is_blank(lines[end - 1]) {
+                    break;
+                } else {
+                    // 字下げのない行は、直前の段落の続きです。
+                    content.push(line.trim_start());
+                }
+                end += 1;
+            }
+            while content.last() == Some(&"") {
+                content.pop();
+            }
+            items.push(content);
+            while end < lines.len() && is_blank(lines[end]) && loose {
+                end += 1;
+            }
+        }
+
+        let tag = if ordered { "ol" } else { "ul" };
+        match start {
+            Some(start) if start != 1 => _ = writeln!(self.html, "<ol start=\"{}\">", start),
+            _ => _ = writeln!(self.html, "<{}>", tag),
+        }
+        let tight = std::mem::replace(&mut self.tight, !loose);
+        for content in items {
+            self.html.push_str("<li>");
+            self.blocks(&content, None);
+            self.html.push_str("</li>\n");
+        }
+        self.tight = tight;
+        _ = writeln!(self.html, "</{}>", tag);
+        end
+    }
+
+    fn table(&mut self, lines: &[&str], i: usize, alignments: &[&str]) -> usize {
+        self.html
+            .push_str("<table border=\"1\" cellspacing=\"0\" cellpadding=\"4\">\n");
+        self.table_row(lines[i], alignments, "th");
+        let mut end = i + 2;
+        while end < lines.len() && !is_blank(lines[end]) && lines[end].contains('|') {
+            self.table_row(lines[end], alignments, "td");
+            end += 1;
+        }
+        self.html.push_str("</table>\n");
+        end
+    }
+
+    fn table_row(&mut self, line: &str, alignments: &[&str], tag: &str) {
+        self.html.push_str("<tr>");
+        let cells = table_cells(line);
+        for (column, alignment) in alignments.iter().enumerate() {
+            let cell = cells
+                .get(column)
+                .map_or(String::new(), |cell| self.inline(cell));
+            _ = write!(
+                self.html,
+                "<{0} align=\"{1}\">{2}</{0}>",
+                tag, alignment, cell
+            );
+        }
+        self.html.push_str("</tr>\n");
+    }
+
+    fn paragraph(&mut self, lines: &[&str], i: usize) -> usize {
+        let mut end = i + 1;
+        while end < lines.len() {
+            let line = lines[end];
+            // 下線を引いた行は見出しです。
+            let underline = line.trim();
+            if !underline.is_empty() && underline.chars().all(|c| c == '=' || c == '-') {
+                let level = if underline.starts_with('=') { 1 } else { 2 };
+                let text = self.inline_lines(&lines[i..end]);
+                _ = writeln!(self.html, "<h{0}>{1}</h{0}>", level, text);
+                return end + 1;
+            }
+            if is_blank(line) || starts_block(line) {
+                break;
+            }
+            end += 1;
+        }
+        let text = self.inline_lines(&lines[i..end]);
+        if self.tight {
+            self.html.push_str(&text);
+            self.html.push('\n');
+        } else {
+            _ = writeln!(self.html, "<p>{}</p>", text);
+        }
+        end
+    }
+
+    /// 段落の行をつなげて変換します。行末の空白 2 つか "\" は改行にします。
+    fn inline_lines(&self, lines: &[&str]) -> String {
+        let mut result = String::new();
+        for (n, line) in lines.iter().enumerate() {
+            let hard_break = line.ends_with("  ") || line.ends_with('\\');
+            let line = line.trim();
+            let line = line.strip_suffix('\\').unwrap_or(line);
+            result.push_str(&self.inline(line));
+            if n + 1 < lines.len() {
+                result.push_str(if hard_break { "<br>\n" } else { "\n" });
+            }
+        }
+        result
+    }
+
+    /// 強調、コード、リンク、画像を変換します。
+    fn inline(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+        let mut previous = None;
+        while let Some(c) = rest.chars().next() {
+            let consumed = match c {
+                '\\' => rest[1..]
+                    .chars()
+                    .next()
+                    .filter(char::is_ascii_punctuation)
+                    .map(|escaped| {
+                        result.push_str(&escape(&escaped.to_string()));
+                        2
+                    }),
+                '`' => code_span(rest, &mut result),
+                '!' if rest[1..].starts_with('[') => {
+                    self.link(&rest[1..], true, &mut result).map(|n| n + 1)
+                }
+                '[' => self.link(rest, false, &mut result),
+                '<' => autolink(rest, &mut result),
+                '*' | '_' => self.emphasis(rest, c, previous, &mut result),
+                _ => None,
+            };
+            let consumed = consumed.unwrap_or_else(|| {
+                result.push_str(&escape(&c.to_string()));
+                c.len_utf8()
+            });
+            previous = rest[..consumed].chars().last();
+            rest = &rest[consumed..];
+        }
+        result
+    }
+
+    /// `[文字列](URL)` をリンクか画像にし、読み進めたバイト数を返します。
+    fn link(&self, text: &str, image: bool, result: &mut String) -> Option<usize> {
+        let close = matching(text, '[', ']')?;
+        let rest = &text[close + 1..];
+        if !rest.starts_with('(') {
+            return None;
+        }
+        let end = matching(rest, '(', ')')?;
+        // タイトル ("…") は使いません。
+        let url = rest[1..end].split_whitespace().next().unwrap_or("");
+        let url = url
+            .strip_prefix('<')
+            .and_then(|url| url.strip_suffix('>'))
+            .unwrap_or(url);
+        let label = &text[1..close];
+        if image {
+            _ = write!(
+                result,
+                "<img src=\"{}\" alt=\"{}\">",
+                escape(&(self.resolve_image)(url)),
+                escape(label)
+            );
+        } else {
+            _ = write!(
+                result,
+                "<a href=\"{}\">{}</a>",
+                escape(url),
+                self.inline(label)
+            );
+        }
+        Some(close + 1 + end + 1)
+    }
+
+    /// `*強調*` と `**強い強調**` を変換し、読み進めたバイト数を返します。
+    fn emphasis(
+        &self,
+        text: &str,
+        marker: char,
+        previous: Option<char>,
+        result: &mut String,
+    ) -> Option<usize> {
+        // snake_case のような単語の中の "_" は強調にしません。
+        if marker == '_' && previous.is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+        let run = text.chars().take_while(|&c| c == marker).count();
+        let (width, tag) = if run >= 2 { (2, "strong") } else { (1, "em") };
+        let delimiter = &text[..width];
+        let body = &text[width..];
+        if body.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let mut search = 0;
+        while let Some(found) = body[search..].find(delimiter) {
+            let mut close = search + found;
+            // "***" のように続くときは、最後の記号で閉じます。
+            while body[close + width..].starts_with(marker) {
+                close += 1;
+            }
+            let inner = &body[..close];
+            let after = body[close + width..].chars().next();
+            let closes = !inner.is_empty()
+                && !inner.ends_with(char::is_whitespace)
+                && (marker != '_' || !after.is_some_and(char::is_alphanumeric));
+            if closes {
+                _ = write!(result, "<{0}>{1}</{0}>", tag, self.inline(inner));
+                return Some(width + close + width);
+            }
+            search = close + width;
+        }
+        None
+    }
+}
+
+/// `` `コード` `` を変換し、読み進めたバイト数を返します。
+fn code_span(text: &str, result: &mut String) -> Option<usize> {
+    let width = text.chars().take_while(|&c| c == '`').count();
+    let ticks = &text[..width];
+    let mut search = width;
+    while let Some(found) = text[search..].find(ticks) {
+        let close = search + found;
+        let run = text[close..].chars().take_while(|&c| c == '`').count();
+        if run == width {
+            let code = &text[width..close];
+            let code = code
+                .strip_prefix(' ')
+                .and_then(|code| code.strip_suffix(' '))
+                .unwrap_or(code);
+            _ = write!(result, "<code>{}</code>", escape(code));
+            return Some(close + width);
+        }
+        search = close + run;
+    }
+    None
+}
+
+/// `<https://…>` をリンクにし、読み進めたバイト数を返します。
+fn autolink(text: &str, result: &mut String) -> Option<usize> {
+    let end = text.find('>')?;
+    let url = &text[1..end];
+    if url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    let href = if url.contains("://") {
+        url.to_owned()
+    } else if url.contains('@') && !url.contains(':') {
+        format!("mailto:{}", url)
+    } else {
+        return None;
+    };
+    _ = write!(result, "<a href=\"{}\">{}</a>", escape(&href), escape(url));
+    Some(end + 1)
+}
+
+/// `text` の先頭の `open` に対応する `close` の位置を返します。
+fn matching(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == open => depth += 1,
+            _ if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+pub fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+fn leading_spaces(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// 行頭の空白が 3 つまでなら、それを除いた行を返します。
+fn unindented(line: &str) -> Option<&str> {
+    (leading_spaces(line) <= 3).then(|| line.trim_start_matches(' '))
+}
+
+/// 4 つの空白かタブで字下げした行なら、字下げを除いた行を返します。
+fn indented(line: &str) -> Option<&str> {
+    line.strip_prefix("    ")
+        .or_else(|| line.strip_prefix('\t'))
+        .filter(|line| !is_blank(line))
+}
+
+fn starts_block(line: &str) -> bool {
+    fence(line).is_some()
+        || heading(line).is_some()
+        || is_rule(line)
+        || quote(line).is_some()
+        || list_item(line).is_some()
+}
+
+/// "```" や "~~~" で始まるコードの行なら、閉じる記号を返します。
+fn fence(line: &str) -> Option<&str> {
+    let line = unindented(line)?;
+    let marker = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let width = line.chars().take_while(|&c| c == marker).count();
+    (width >= 3).then(|| &line[..width])
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let line = unindented(line)?;
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = &line[level..];
+    if !(1..=6).contains(&level) || !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+    // 末尾の "#" は飾りなので取り除きます。
+    let text = text.trim();
+    let trimmed = text.trim_end_matches('#');
+    let text = if trimmed.is_empty() || trimmed.ends_with(' ') {
+        trimmed.trim_end()
+    } else {
+        text
+    };
+    Some((level, text))
+}
+
+fn is_rule(line: &str) -> bool {
+    let Some(line) = unindented(line) else {
+        return false;
+    };
+    let marks: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&mark| marks.chars().all(|c| c == mark))
+}
+
+fn quote(line: &str) -> Option<&str> {
+    let line = unindented(line)?.strip_prefix('>')?;
+    Some(line.strip_prefix(' ').unwrap_or(line))
+}
+
+struct ListItem<'a> {
+    ordered: bool,
+    number: Option<usize>,
+    /// 項目の続きの行に必要な字下げ
+    indent: usize,
+    text: &'a str,
+}
+
+fn list_item(line: &str) -> Option<ListItem<'_>> {
+    let body = unindented(line)?;
+    let lead = line.len() - body.len();
+    let digits = body.chars().take_while(char::is_ascii_digit).count();
+    let (ordered, number, marker) = if digits > 0 && digits <= 9 {
+        let rest = &body[digits..];
+        if !(rest.starts_with('.') || rest.starts_with(')')) {
+            return None;
+        }
+        (true, body[..digits].parse().ok(), digits + 1)
+    } else if body.starts_with(['-', '*', '+']) {
+        (false, None, 1)
+    } else {
+        return None;
+    };
+    let rest = &body[marker..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    let text = rest.trim_start();
+    let spaces = (rest.len() - text.len()).clamp(1, 4);
+    Some(ListItem {
+        ordered,
+        number,
+        indent: lead + marker + spaces,
+        text,
+    })
+}
+
+/// 表の区切りの行 ("|---|:---:|") なら、列ごとの寄せ方を返します。
+fn table_alignments(line: &str) -> Option<Vec<&'static str>> {
+    if !line.contains('-') {
+        return None;
+    }
+    table_cells(line)
+        .iter()
+        .map(|cell| {
+            let left = cell.starts_with(':');
+            let right = cell.ends_with(':');
+            let dashes = cell.trim_matches(':');
+            if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+                return None;
+            }
+            Some(match (left, right) {
+                (true, true) => "center",
+                (false, true) => "right",
+                _ => "left",
+            })
+        })
+        .collect()
+}
+
+fn table_cells(line: &str) -> Vec<&str> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(str::trim).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn html(text: &str) -> String {
+        let rendered = to_html(text, &|src: &str| format!("/doc/{}", src));
+        // アンカーは別に確かめます。
+        let mut html = rendered.html;
+        for line in rendered.lines {
+            html = html.replace(&format!("<a name=\"{}\"></a>", anchor(line)), "");
+        }
+        html
+    }
+
+    #[test]
+    fn headings_and_paragraphs() {
+        assert_eq!(
+            html("# 見出し #\n\n本文の\n続き\n"),
+            "<h1>見出し</h1>\n<p>本文の\n続き</p>\n"
+        );
+        assert_eq!(html("題名\n===\n"), "<h1>題名</h1>\n");
+        assert_eq!(html("#ハッシュタグ"), "<p>#ハッシュタグ</p>\n");
+        assert_eq!(html("一行目  \n二行目"), "<p>一行目<br>\n二行目</p>\n");
+    }
+
+    #[test]
+    fn inline_markup() {
+        assert_eq!(
+            html("**強調** と *斜体* と `a < b`"),
+            "<p><strong>強調</strong> と <em>斜体</em> と <code>a &lt; b</code></p>\n"
+        );
+        assert_eq!(
+            html("***両方***"),
+            "<p><strong><em>両方</em></strong></p>\n"
+        );
+        // 単語の中の "_" や、閉じていない記号はそのまま
+        assert_eq!(
+            html("snake_case_name と 2 * 3"),
+            "<p>snake_case_name と 2 * 3</p>\n"
+        );
+        assert_eq!(html(r"\*記号\*"), "<p>*記号*</p>\n");
+    }
+
+    #[test]
+    fn links_and_images() {
+        assert_eq!(
+            html("[サイト](https://example.com \"題\") ![図](img/a.png)"),
+            "<p><a href=\"https://example.com\">サイト</a> \
+             <img src=\"/doc/img/a.png\" alt=\"図\"></p>\n"
+        );
+        assert_eq!(
+            html("<https://example.com>"),
+            "<p><a href=\"https://example.com\">https://example.com</a></p>\n"
+        );
+        assert_eq!(html("[括弧] だけ"), "<p>[括弧] だけ</p>\n");
+    }
+
+    #[test]
+    fn code_blocks() {
+        assert_eq!(
+            html("```rust\nfn main() {}\n<tag>\n```\n後"),
+            "<pre><code>fn main() {}\n&lt;tag&gt;\n</code></pre>\n<p>後</p>\n"
+        );
+        assert_eq!(
+            html("    let a = 1;\n\n    a\n"),
+            "<pre><code>let a = 1;\n\na\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn lists() {
+        // Given: 入れ子の箇条書きと番号付きのリスト
+        let text = "- 一\n- 二\n  - 二の一\n\n3. 三\n4. 四\n";
+        // When/Then: 空行のないリストは <p> で囲まない
+        assert_eq!(
+            html(text),
+            "<ul>\n<li>一\n</li>\n<li>二\n<ul>\n<li>二の一\n</li>\n</ul>\n</li>\n</ul>\n\
+             <ol start=\"3\">\n<li>三\n</li>\n<li>四\n</li>\n</ol>\n"
+        );
+        // 空行で区切ったリストは段落にする
+        assert_eq!(
+            html("- 一\n\n- 二\n"),
+            "<ul>\n<li><p>一</p>\n</li>\n<li><p>二</p>\n</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn quotes_rules_and_tables() {
+        assert_eq!(
+            html("> 引用\n> 続き\n\n---\n"),
+            "<blockquote>\n<p>引用\n続き</p>\n</blockquote>\n<hr>\n"
+        );
+        assert_eq!(
+            html("| 名前 | 数 |\n|:--|--:|\n| a | 1 |\n"),
+            "<table border=\"1\" cellspacing=\"0\" cellpadding=\"4\">\n\
+             <tr><th align=\"left\">名前</th><th align=\"right\">数</th></tr>\n\
+             <tr><td align=\"left\">a</td><td align=\"right\">1</td></tr>\n\
+             </table>\n"
+        );
+    }
+
+    #[test]
+    fn anchors_mark_block_lines() {
+        // Given: 空行を挟んだ 3 つのブロック
+        let rendered = to_html("# 題\n\n本文\n続き\n\n- 項目\n", &|src: &str| {
+            src.to_owned()
+        });
+        // When/Then: 各ブロックの最初の行にアンカーを置く
+        assert_eq!(rendered.lines, vec![0, 2, 5]);
+        assert!(rendered.html.starts_with("<a name=\"L0\"></a><h1>"));
+    }
+}
@@ -0,0 +1,103 @@
+// Markdown の文書を HTML にして、エディタの横に表示します。
+
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_int;
+use std::path::Path;
+use std::rc::Rc;
+
+use wx;
+use wx::methods::*;
+
+use crate::markdown;
+
+// 入力が止まってから表示し直すまでの時間
+const REFRESH_DELAY_MS: c_int = 300;
+
+pub struct MarkdownPreview {
+    html: wx::HtmlWindow,
+    timer: wx::Timer,
+    // 表示中の HTML にアンカーを置いた行
+    anchors: RefCell<Vec<usize>>,
+    // 最後に表示位置を合わせた、エディタの一番上の行
+    synced_line: Cell<Option<i32>>,
+}
+impl MarkdownPreview {
+    /// `on_refresh` は、入力が止まって表示し直すときに呼びます。
+    pub fn new<W: WindowMethods, F: Fn() + 'static>(parent: &W, on_refresh: F) -> Rc<Self> {
+        // PNG や JPEG の画像を表示できるようにします。
+        wx::init_all_image_handlers();
+        let html = wx::HtmlWindow::builder(Some(parent))
+            .style(wx::HW_SCROLLBAR_AUTO.into())
+            .build();
+        html.show(false);
+        let timer = wx::Timer::new();
+        timer.bind(wx::RustEvent::Timer, move |_: &wx::TimerEvent| on_refresh());
+        Rc::new(Self {
+            html,
+            timer,
+            anchors: RefCell::new(Vec::new()),
+            synced_line: Cell::new(None),
+        })
+    }
+
+    pub fn window(&self) -> &wx::HtmlWindow {
+        &self.html
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.html.is_shown()
+    }
+
+    pub fn show(&self, visible: bool) {
+        self.html.show(visible);
+        if !visible {
+            self.timer.stop();
+        }
+    }
+
+    /// 入力が続いている間は表示し直さず、止まってから `on_refresh` を呼びます。
+    pub fn schedule_refresh(&self) {
+        self.timer.start(REFRESH_DELAY_MS, wx::TIMER_ONE_SHOT);
+    }
+
+    /// `text` を表示します。画像の相対パスは文書の `path` から探します。
+    pub fn render(&self, text: &str, path: Option<&str>) {
+        let base = path.and_then(|path| Path::new(path).parent());
+        let rendered = markdown::to_html(text, &|src: &str| image_url(src, base));
+        self.html
+            .set_page(&format!("<html><body>{}</body></html>", rendered.html));
+        *self.anchors.borrow_mut() = rendered.lines;
+        // 表示し直すと先頭に戻るので、次の同期で位置を合わせ直します。
+        self.synced_line.set(None);
+    }
+
+    /// エディタの一番上の行 `line` を含むブロックまでスクロールします。
+    pub fn sync_scroll(&self, line: i32) {
+        if !self.is_shown() || self.synced_line.get() == Some(line) {
+            return;
+        }
+        self.synced_line.set(Some(line));
+        let anchors = self.anchors.borrow();
+        let count = anchors.partition_point(|&anchor| anchor <= line.max(0) as usize);
+        match count.checked_sub(1) {
+            Some(i) if i > 0 => {
+                self.html.scroll_to_anchor(&markdown::anchor(anchors[i]));
+            }
+            _ => self.html.scroll(0, 0),
+        }
+    }
+}
+
+/// 画像の相対パスを、文書のあるディレクトリ `base` からの URL にします。
+fn image_url(src: &str, base: Option<&Path>) -> String {
+    if src.contains("://") || src.starts_with("data:") {
+        return src.to_owned();
+    }
+    match base {
+        Some(base) => {
+            let path = base.join(src);
+            wx::FileSystem::file_name_to_url(&wx::FileName::new_with_str(&path.to_string_lossy()))
+        }
+        None => src.to_owned(),
+    }
+}
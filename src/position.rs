@@ -0,0 +1,96 @@
+// Rust の文字列は UTF-8 のバイト位置で扱いますが、wx::TextCtrl の位置は
+// プラットフォームによって UTF-16 のコード単位 (Windows, macOS) か
+// Unicode のコードポイント (GTK) で数えます。その間を変換します。
+// wx::StyledTextCtrl は UTF-8 のバイト位置をそのまま使います。
+
+use std::os::raw::c_long;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Unit {
+    Utf16,
+    Char,
+    Byte,
+}
+
+/// このプラットフォームの wx::TextCtrl が使う単位
+pub const NATIVE: Unit = if cfg!(any(windows, target_os = "macos")) {
+    Unit::Utf16
+} else {
+    Unit::Char
+};
+
+fn width(c: char, unit: Unit) -> c_long {
+    match unit {
+        Unit::Utf16 => c.len_utf16() as c_long,
+        Unit::Char => 1,
+        Unit::Byte => c.len_utf8() as c_long,
+    }
+}
+
+/// UTF-8 のバイト位置を wx::TextCtrl の位置に変換します。
+/// 文字の途中を指していたら、その文字の先頭に丸めます。
+pub fn from_byte(text: &str, byte: usize, unit: Unit) -> c_long {
+    let mut byte = byte.min(text.len());
+    while !text.is_char_boundary(byte) {
+        byte -= 1;
+    }
+    text[..byte].chars().map(|c| width(c, unit)).sum()
+}
+
+/// wx::TextCtrl の位置を UTF-8 のバイト位置に変換します。
+/// サロゲートペアの途中を指していたら、その文字の先頭に丸めます。
+pub fn to_byte(text: &str, pos: c_long, unit: Unit) -> usize {
+    let mut current = 0;
+    for (byte, c) in text.char_indices() {
+        let next = current + width(c, unit);
+        if pos < next {
+            return byte;
+        }
+        current = next;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ascii_positions_are_bytes() {
+        for unit in [Unit::Utf16, Unit::Char] {
+            assert_eq!(from_byte("abc", 2, unit), 2);
+            assert_eq!(to_byte("abc", 2, unit), 2);
+        }
+    }
+
+    #[test]
+    fn japanese_positions() {
+        // 「日本語」は 1 文字 3 バイト
+        assert_eq!(from_byte("日本語", 6, Unit::Char), 2);
+        assert_eq!(from_byte("日本語", 6, Unit::Utf16), 2);
+        assert_eq!(to_byte("日本語", 2, Unit::Char), 6);
+        assert_eq!(to_byte("日本語", 2, Unit::Utf16), 6);
+        assert_eq!(from_byte("日本語", 6, Unit::Byte), 6);
+        // 文字の途中を指すバイト位置は、その文字の先頭に丸める
+        assert_eq!(to_byte("日本語", 4, Unit::Byte), 3);
+    }
+
+    #[test]
+    fn surrogate_pair_positions() {
+        // 「𠮷」は UTF-16 でサロゲートペア、UTF-8 で 4 バイト
+        let text = "𠮷野家";
+        assert_eq!(from_byte(text, 4, Unit::Utf16), 2);
+        assert_eq!(from_byte(text, 4, Unit::Char), 1);
+        assert_eq!(to_byte(text, 2, Unit::Utf16), 4);
+        assert_eq!(to_byte(text, 1, Unit::Utf16), 0);
+        assert_eq!(to_byte(text, 1, Unit::Char), 4);
+    }
+
+    #[test]
+    fn out_of_range_positions_are_clamped() {
+        assert_eq!(to_byte("abc", 10, Unit::Char), 3);
+        assert_eq!(from_byte("abc", 10, Unit::Char), 3);
+        // 読み込み直して文字の途中を指すようになった位置
+        assert_eq!(from_byte("日本語", 4, Unit::Char), 1);
+    }
+}
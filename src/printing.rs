@@ -0,0 +1,339 @@
+use std::cell::RefCell;
+use std::mem;
+use std::os::raw::c_int;
+use std::rc::Rc;
+
+use wx;
+use wx::methods::*;
+
+pub const DEFAULT_HEADER: &str = "&f";
+pub const DEFAULT_FOOTER: &str = "&p ページ";
+
+/// ヘッダーとフッターの書式コードに埋め込む値
+pub struct HeaderFields<'a> {
+    pub file_name: &'a str,
+    pub page: usize,
+    pub date: &'a str,
+    pub time: &'a str,
+}
+
+/// 左寄せ、中央揃え、右寄せに分けたヘッダー(またはフッター)の行
+#[derive(Debug, Default, PartialEq)]
+pub struct HeaderLine {
+    pub left: String,
+    pub center: String,
+    pub right: String,
+}
+
+/// メモ帳と同じ書式コードを展開します。
+///
+/// `&f` ファイル名、`&p` ページ番号、`&d` 日付、`&t` 時刻、`&&` アンパサンド、
+/// `&l` `&c` `&r` は以降の文字列を左寄せ、中央揃え、右寄せにします。
+/// 位置の指定がなければ中央揃えです。
+pub fn expand_codes(template: &str, fields: &HeaderFields) -> HeaderLine {
+    let mut line = HeaderLine::default();
+    let mut target = &mut line.center;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '&' {
+            target.push(c);
+            continue;
+        }
+        match chars.next().map(|c| c.to_ascii_lowercase()) {
+            Some('f') => target.push_str(fields.file_name),
+            Some('p') => target.push_str(&fields.page.to_string()),
+            Some('d') => target.push_str(fields.date),
+            Some('t') => target.push_str(fields.time),
+            Some('&') => target.push('&'),
+            Some('l') => target = &mut line.left,
+            Some('c') => target = &mut line.center,
+            Some('r') => target = &mut line.right,
+            // 知らないコードはそのまま出力します。
+            Some(other) => {
+                target.push('&');
+                target.push(other);
+            }
+            None => target.push('&'),
+        }
+    }
+    line
+}
+
+/// 1 行を `max_width` に収まるよう折り返します。
+/// 1 文字も収まらない場合でも最低 1 文字は置きます。
+/// 長い行でも測り直しを繰り返さないよう、1 文字ずつ測った幅を足していきます。
+pub fn wrap_line<M: Fn(&str) -> i32>(line: &str, max_width: i32, measure: &M) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut width = 0;
+    let mut buf = [0; 4];
+    for c in line.chars() {
+        let char_width = measure(c.encode_utf8(&mut buf));
+        if width + char_width > max_width && !current.is_empty() {
+            result.push(mem::take(&mut current));
+            width = 0;
+        }
+        current.push(c);
+        width += char_width;
+    }
+    if !current.is_empty() || result.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+/// テキストをページごとの行に分けます。
+/// `max_width` が `Some` なら、その幅で折り返します。
+pub fn paginate<M: Fn(&str) -> i32>(
+    text: &str,
+    lines_per_page: usize,
+    max_width: Option<i32>,
+    measure: &M,
+) -> Vec<Vec<String>> {
+    let lines_per_page = lines_per_page.max(1);
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        match max_width {
+            Some(max_width) => lines.extend(wrap_line(line, max_width, measure)),
+            None => lines.push(line.to_owned()),
+        }
+    }
+    if lines.is_empty() {
+        // 空の文書でも 1 ページは印刷します。
+        return vec![Vec::new()];
+    }
+    lines
+        .chunks(lines_per_page)
+        .map(|page| page.to_vec())
+        .collect()
+}
+
+/// `EditorCtrl` のテキストを印刷する `wx::Printout` の中身です。
+pub struct TextPrintout {
+    text: String,
+    font: wx::Font,
+    word_wrap: bool,
+    file_name: String,
+    header: String,
+    footer: String,
+    date: String,
+    time: String,
+    page_setup: wx::PageSetupDialogData,
+    pages: RefCell<Vec<Vec<String>>>,
+}
+impl TextPrintout {
+    pub fn new(
+        text: String,
+        font: wx::Font,
+        word_wrap: bool,
+        file_name: &str,
+        header: &str,
+        footer: &str,
+        page_setup: &wx::PageSetupDialogData,
+    ) -> Rc<Self> {
+        let now = wx::DateTime::now();
+        Rc::new(Self {
+            text,
+            font,
+            word_wrap,
+            file_name: file_name.to_owned(),
+            header: header.to_owned(),
+            footer: footer.to_owned(),
+            date: now.format_date(),
+            time: now.format_time(),
+            page_setup: page_setup.clone(),
+            pages: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// wx::Printout の仮想関数をこの構造体のメソッドにつないで返します。
+    pub fn create(self: &Rc<Self>) -> wx::Printout {
+        let printout = wx::Printout::new(&self.file_name);
+        let this = self.clone();
+        printout.on_prepare_printing(move |printout: &wx::Printout| {
+            this.prepare(printout);
+        });
+        let this = self.clone();
+        printout.on_get_page_info(move |_: &wx::Printout| {
+            let count = this.pages.borrow().len() as c_int;
+            (1, count, 1, count)
+        });
+        let this = self.clone();
+        printout.on_has_page(move |_: &wx::Printout, page: c_int| {
+            1 <= page && page as usize <= this.pages.borrow().len()
+        });
+        let this = self.clone();
+        printout.on_print_page(move |printout: &wx::Printout, page: c_int| {
+            this.print_page(printout, page as usize)
+        });
+        printout
+    }
+
+    fn body_rect(&self, printout: &wx::Printout, dc: &wx::DC) -> (wx::Rect, c_int) {
+        let rect = printout.get_logical_page_margins_rect(&self.page_setup);
+        let line_height = dc.get_char_height();
+        // ヘッダーとフッターに 1 行ずつと、本文との間に 1 行ずつ空けます。
+        let body = wx::Rect::new_with_int(
+            rect.get_x(),
+            rect.get_y() + line_height * 2,
+            rect.get_width(),
+            rect.get_height() - line_height * 4,
+        );
+        (body, line_height)
+    }
+
+    fn prepare(&self, printout: &wx::Printout) {
+        printout.map_screen_size_to_page();
+        let dc = match printout.get_dc() {
+            Some(dc) => dc,
+            None => return,
+        };
+        dc.set_font(&self.font);
+        let (body, line_height) = self.body_rect(printout, &dc);
+        let lines_per_page = (body.get_height() / line_height.max(1)).max(1) as usize;
+        let max_width = if self.word_wrap {
+            Some(body.get_width())
+        } else {
+            None
+        };
+        let measure = |s: &str| dc.get_text_extent(s).get_width();
+        *self.pages.borrow_mut() = paginate(&self.text, lines_per_page, max_width, &measure);
+    }
+
+    fn print_page(&self, printout: &wx::Printout, page: usize) -> bool {
+        let dc = match printout.get_dc() {
+            Some(dc) => dc,
+            None => return false,
+        };
+        printout.map_screen_size_to_page();
+        dc.set_font(&self.font);
+        let (body, line_height) = self.body_rect(printout, &dc);
+        let pages = self.pages.borrow();
+        let lines = match pages.get(page - 1) {
+            Some(lines) => lines,
+            None => return false,
+        };
+        for (i, line) in lines.iter().enumerate() {
+            dc.draw_text(line, body.get_x(), body.get_y() + line_height * i as c_int);
+        }
+
+        let fields = HeaderFields {
+            file_name: &self.file_name,
+            page,
+            date: &self.date,
+            time: &self.time,
+        };
+        let top = body.get_y() - line_height * 2;
+        let bottom = body.get_y() + body.get_height() + line_height;
+        self.draw_header_line(&dc, &expand_codes(&self.header, &fields), &body, top);
+        self.draw_header_line(&dc, &expand_codes(&self.footer, &fields), &body, bottom);
+        true
+    }
+
+    fn draw_header_line(&self, dc: &wx::DC, line: &HeaderLine, body: &wx::Rect, y: c_int) {
+        let width = |s: &str| dc.get_text_extent(s).get_width();
+        let left = body.get_x();
+        let right = body.get_x() + body.get_width();
+        dc.draw_text(&line.left, left, y);
+        dc.draw_text(
+            &line.center,
+            left + (body.get_width() - width(&line.center)) / 2,
+            y,
+        );
+        dc.draw_text(&line.right, right - width(&line.right), y);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::Cell;
+
+    fn fields() -> HeaderFields<'static> {
+        HeaderFields {
+            file_name: "memo.txt",
+            page: 3,
+            date: "2022/05/01",
+            time: "12:34",
+        }
+    }
+
+    // 1 文字を幅 1 として測ります。
+    fn char_count(s: &str) -> i32 {
+        s.chars().count() as i32
+    }
+
+    #[test]
+    fn expand_codes_defaults_to_center() {
+        let line = expand_codes("&f", &fields());
+        assert_eq!(line.center, "memo.txt");
+        assert!(line.left.is_empty());
+        assert!(line.right.is_empty());
+    }
+
+    #[test]
+    fn expand_codes_with_alignment() {
+        let line = expand_codes("&l&f&c&p ページ&r&d &t", &fields());
+        assert_eq!(
+            line,
+            HeaderLine {
+                left: "memo.txt".to_owned(),
+                center: "3 ページ".to_owned(),
+                right: "2022/05/01 12:34".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn expand_codes_keeps_ampersand_and_unknown_codes() {
+        let line = expand_codes("A && B &x&", &fields());
+        assert_eq!(line.center, "A & B &x&");
+    }
+
+    #[test]
+    fn wrap_line_splits_by_width() {
+        assert_eq!(
+            wrap_line("あいうえおか", 4, &char_count),
+            vec!["あいうえ", "おか"]
+        );
+        assert_eq!(wrap_line("", 4, &char_count), vec![""]);
+    }
+
+    #[test]
+    fn wrap_line_measures_each_character_once() {
+        // Given: 長い行を
+        let line = "あ".repeat(1000);
+        let measured = Cell::new(0);
+        let measure = |s: &str| {
+            measured.set(measured.get() + s.chars().count());
+            char_count(s)
+        };
+        // When: 折り返すと
+        let lines = wrap_line(&line, 80, &measure);
+        // Then: 測るのは 1 文字につき 1 回だけ
+        assert_eq!(lines.len(), 13);
+        assert_eq!(measured.get(), 1000);
+    }
+
+    #[test]
+    fn paginate_without_wrap() {
+        let pages = paginate("1\n2\n3\n4\n5", 2, None, &char_count);
+        assert_eq!(pages, vec![vec!["1", "2"], vec!["3", "4"], vec!["5"]]);
+    }
+
+    #[test]
+    fn paginate_with_wrap() {
+        // Given: 折り返すと 3 行になるテキストを
+        let text = "abcdef\ng";
+        // When: 1 ページ 2 行、幅 4 で分けると
+        let pages = paginate(text, 2, Some(4), &char_count);
+        // Then: 折り返した行もページに数えられる
+        assert_eq!(pages, vec![vec!["abcd", "ef"], vec!["g"]]);
+    }
+
+    #[test]
+    fn empty_text_has_one_page() {
+        assert_eq!(paginate("", 10, None, &char_count).len(), 1);
+    }
+}
@@ -0,0 +1,531 @@
+use std::fmt;
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Clone, Default)]
+pub struct SearchOptions {
+    pub match_case: bool,
+    /// 検索する文字列を正規表現として扱い、置換後の文字列の `$1` などを展開します。
+    pub regex: bool,
+    /// `^` と `$` を各行の先頭と末尾に一致させます。正規表現のときだけ使います。
+    pub multiline: bool,
+    pub wrap_around: bool,
+    /// 前後が単語の境界になっている一致だけを探します。
+    pub whole_word: bool,
+    /// ダイアログを開いたときの選択範囲の中だけを探します。
+    pub in_selection: bool,
+}
+
+/// 検索と置換の条件。F3 で同じ条件の検索を繰り返せるよう保持しておきます。
+#[derive(Clone, Default)]
+pub struct FindQuery {
+    pub pattern: String,
+    pub replacement: String,
+    pub options: SearchOptions,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+#[derive(Debug)]
+pub struct InvalidPattern(String);
+impl fmt::Display for InvalidPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "正規表現が正しくありません: {}", self.0)
+    }
+}
+
+pub struct Searcher {
+    regex: Regex,
+    expand: bool,
+    wrap_around: bool,
+    whole_word: bool,
+    scope: Option<Range<usize>>,
+}
+impl Searcher {
+    pub fn new(query: &FindQuery) -> Result<Self, InvalidPattern> {
+        let options = &query.options;
+        let pattern = if options.regex {
+            query.pattern.clone()
+        } else {
+            regex::escape(&query.pattern)
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.match_case)
+            .multi_line(options.regex && options.multiline)
+            .build()
+            .map_err(|e| InvalidPattern(e.to_string()))?;
+        Ok(Self {
+            regex,
+            expand: options.regex,
+            wrap_around: options.wrap_around,
+            whole_word: options.whole_word,
+            scope: None,
+        })
+    }
+
+    /// 検索する範囲を `scope` (UTF-8 のバイト位置) の中に限ります。
+    /// 折り返すときも、範囲の先頭 (または末尾) に戻ります。
+    pub fn with_scope(mut self, scope: Range<usize>) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// 検索する範囲。テキストの長さと文字の境界に収めます。
+    fn scope(&self, text: &str) -> Range<usize> {
+        let Some(scope) = &self.scope else {
+            return 0..text.len();
+        };
+        let floor = |mut pos: usize| {
+            pos = pos.min(text.len());
+            while !text.is_char_boundary(pos) {
+                pos -= 1;
+            }
+            pos
+        };
+        let end = floor(scope.end);
+        floor(scope.start).min(end)..end
+    }
+
+    /// `from` 以降で最初に一致する範囲を返します。
+    /// `from` の位置の空の一致は、同じ場所に止まり続けないよう飛ばします。
+    pub fn find_next(&self, text: &str, from: usize) -> Option<Range<usize>> {
+        let found = self.find_from(text, from, true);
+        if found.is_none() && self.wrap_around {
+            let start = self.scope(text).start;
+            return self
+                .find_from(text, start, false)
+                .filter(|m| m.start < from);
+        }
+        found
+    }
+
+    fn find_from(&self, text: &str, from: usize, skip_empty: bool) -> Option<Range<usize>> {
+        let scope = self.scope(text);
+        // 範囲の末尾で切ったものを検索するので、`$` は範囲の末尾にも一致します。
+        let haystack = &text[..scope.end];
+        let mut start = from.max(scope.start);
+        while start <= haystack.len() {
+            let m = self.regex.find_at(haystack, start)?.range();
+            let skipped = skip_empty && m.start == from && m.is_empty();
+            if !skipped && self.is_whole_word(text, &m) {
+                return Some(m);
+            }
+            start = next_boundary(text, m.start);
+        }
+        None
+    }
+
+    /// `before` より前で終わる一致のうち、最後のものを返します。
+    pub fn find_previous(&self, text: &str, before: usize) -> Option<Range<usize>> {
+        let found = self
+            .find_all(text)
+            .into_iter()
+            .take_while(|m| m.start < before)
+            .filter(|m| m.end <= before && !(m.is_empty() && m.start == before))
+            .last();
+        if found.is_none() && self.wrap_around {
+            return self
+                .find_all(text)
+                .into_iter()
+                .last()
+                .filter(|m| m.end > before);
+        }
+        found
+    }
+
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        let mut found = Vec::new();
+        let mut next = self.find_from(text, 0, false);
+        while let Some(m) = next {
+            next = self.find_from(text, m.end, true);
+            found.push(m);
+        }
+        found
+    }
+
+    /// `range` が検索文字列に一致していれば、置き換える文字列を返します。
+    pub fn replacement_for(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        replacement: &str,
+    ) -> Option<String> {
+        let scope = self.scope(text);
+        if range.start < scope.start || range.end > scope.end || !self.is_whole_word(text, &range) {
+            return None;
+        }
+        self.expand_at(text, range, replacement)
+    }
+
+    fn expand_at(&self, text: &str, range: Range<usize>, replacement: &str) -> Option<String> {
+        // 範囲の外の文字も見て判定するため、範囲の先頭から検索します。
+        let haystack = &text[..self.scope(text).end];
+        let captures = self.regex.captures_at(haystack, range.start)?;
+        let m = captures.get(0)?;
+        if m.range() != range {
+            return None;
+        }
+        if !self.expand {
+            return Some(replacement.to_owned());
+        }
+        let mut result = String::new();
+        captures.expand(replacement, &mut result);
+        Some(result)
+    }
+
+    /// すべての一致を置き換えた文字列と、置き換えた数を返します。
+    pub fn replace_all(&self, text: &str, replacement: &str) -> (String, usize) {
+        let found = self.find_all(text);
+        let mut result = String::with_capacity(text.len());
+        let mut last = 0;
+        for m in &found {
+            result.push_str(&text[last..m.start]);
+            match self.expand_at(text, m.clone(), replacement) {
+                Some(replaced) => result.push_str(&replaced),
+                None => result.push_str(&text[m.clone()]),
+            }
+            last = m.end;
+        }
+        result.push_str(&text[last..]);
+        (result, found.len())
+    }
+
+    /// 単語単位で探すとき、一致の前後が単語の境界になっているかを調べます。
+    fn is_whole_word(&self, text: &str, range: &Range<usize>) -> bool {
+        if !self.whole_word {
+            return true;
+        }
+        let before = text[..range.start].chars().next_back();
+        let first = text[range.clone()].chars().next();
+        let last = text[range.clone()].chars().next_back();
+        let after = text[range.end..].chars().next();
+        is_boundary(before, first) && is_boundary(last, after)
+    }
+}
+
+/// `from` 以降で最初に始まる一致の番号を返します。なければ先頭に折り返します。
+pub fn first_from(matches: &[Range<usize>], from: usize) -> Option<usize> {
+    if matches.is_empty() {
+        return None;
+    }
+    Some(matches.iter().position(|m| m.start >= from).unwrap_or(0))
+}
+
+/// `from` の前後にある一致を、多くても `max` 個返します。
+pub fn nearby(matches: &[Range<usize>], from: usize, max: usize) -> &[Range<usize>] {
+    let center = matches.partition_point(|m| m.start < from);
+    let start = center
+        .saturating_sub(max / 2)
+        .min(matches.len().saturating_sub(max));
+    &matches[start..(start + max).min(matches.len())]
+}
+
+/// 選択範囲がちょうど一致の一つなら、その番号を返します。
+pub fn index_of(matches: &[Range<usize>], selection: &Range<usize>) -> Option<usize> {
+    matches
+        .binary_search_by_key(&selection.start, |m| m.start)
+        .ok()
+        .filter(|&i| matches[i] == *selection)
+}
+
+/// 単語を作る文字の種類。種類が変わるところを単語の境界とみなします。
+/// 日本語は分かち書きしないため、漢字とひらがなのように文字の種類が
+/// 変わるところで区切ります。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WordClass {
+    Alphanumeric,
+    Hiragana,
+    Katakana,
+    Han,
+    Hangul,
+}
+
+pub fn word_class(c: char) -> Option<WordClass> {
+    match c {
+        '\u{3041}'..='\u{309F}' => Some(WordClass::Hiragana),
+        // 長音符「ー」と半角カタカナを含みます。中点「・」(U+30FB) は含みません。
+        '\u{30A0}'..='\u{30FA}'
+        | '\u{30FC}'..='\u{30FF}'
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{FF66}'..='\u{FF9F}' => Some(WordClass::Katakana),
+        // 「々」と「〇」を含みます。
+        '\u{3005}'
+        | '\u{3007}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{3FFFF}' => Some(WordClass::Han),
+        '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' | '\u{AC00}'..='\u{D7AF}' => {
+            Some(WordClass::Hangul)
+        }
+        c if c.is_alphanumeric() || c == '_' => Some(WordClass::Alphanumeric),
+        _ => None,
+    }
+}
+
+/// `left` と `right` の間が単語の境界なら真を返します。
+/// 一致の端が記号や空白なら、その側は境界を問いません。
+fn is_boundary(left: Option<char>, right: Option<char>) -> bool {
+    match (left.and_then(word_class), right.and_then(word_class)) {
+        (Some(l), Some(r)) => l != r,
+        _ => true,
+    }
+}
+
+fn next_boundary(text: &str, pos: usize) -> usize {
+    text[pos..]
+        .chars()
+        .next()
+        .map_or(text.len() + 1, |c| pos + c.len_utf8())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn searcher(pattern: &str, options: SearchOptions) -> Searcher {
+        Searcher::new(&FindQuery {
+            pattern: pattern.to_owned(),
+            replacement: String::new(),
+            options,
+        })
+        .unwrap()
+    }
+
+    fn regex_options() -> SearchOptions {
+        SearchOptions {
+            regex: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn literal_search_escapes_metacharacters() {
+        let s = searcher("a.b", SearchOptions::default());
+        assert_eq!(s.find_next("axb a.b", 0), Some(4..7));
+    }
+
+    #[test]
+    fn ignore_case_by_default() {
+        let s = searcher("abc", SearchOptions::default());
+        assert_eq!(s.find_next("xABC", 0), Some(1..4));
+        let s = searcher(
+            "abc",
+            SearchOptions {
+                match_case: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(s.find_next("xABC", 0), None);
+    }
+
+    #[test]
+    fn find_next_and_previous_with_japanese() {
+        let text = "あいうあいう";
+        let s = searcher("い", SearchOptions::default());
+        assert_eq!(s.find_next(text, 0), Some(3..6));
+        assert_eq!(s.find_next(text, 6), Some(12..15));
+        assert_eq!(s.find_previous(text, 12), Some(3..6));
+        assert_eq!(s.find_previous(text, 3), None);
+    }
+
+    #[test]
+    fn wrap_around() {
+        let s = searcher(
+            "a",
+            SearchOptions {
+                wrap_around: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(s.find_next("abca", 1), Some(3..4));
+        assert_eq!(s.find_next("abca", 4), Some(0..1));
+        assert_eq!(s.find_previous("abca", 0), Some(3..4));
+    }
+
+    #[test]
+    fn invalid_regex_is_reported() {
+        let result = Searcher::new(&FindQuery {
+            pattern: "(".to_owned(),
+            replacement: String::new(),
+            options: regex_options(),
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_match_does_not_stick() {
+        // Given: 空文字列に一致する正規表現で
+        let s = searcher("x*", regex_options());
+        // When: 一致した位置から次を検索しても
+        // Then: 同じ位置には止まらない
+        assert_eq!(s.find_next("ab", 0), Some(1..1));
+        assert_eq!(s.find_next("ab", 1), Some(2..2));
+        assert_eq!(s.find_next("ab", 2), None);
+    }
+
+    #[test]
+    fn multiline_anchors() {
+        let text = "foo\nbar\n";
+        let s = searcher("^bar$", regex_options());
+        assert_eq!(s.find_next(text, 0), None);
+        let s = searcher(
+            "^bar$",
+            SearchOptions {
+                regex: true,
+                multiline: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(s.find_next(text, 0), Some(4..7));
+    }
+
+    #[test]
+    fn replace_with_capture_groups() {
+        let s = searcher(r"(\d+)年(\d+)月", regex_options());
+        let text = "2022年5月";
+        assert_eq!(
+            s.replacement_for(text, 0..text.len(), "$2/$1"),
+            Some("5/2022".to_owned())
+        );
+        assert_eq!(s.replacement_for(text, 0..4, "$2/$1"), None);
+        assert_eq!(
+            s.replace_all("2022年5月 2023年6月", "${1}-$2"),
+            ("2022-5 2023-6".to_owned(), 2)
+        );
+    }
+
+    #[test]
+    fn literal_replacement_is_not_expanded() {
+        let s = searcher("a", SearchOptions::default());
+        assert_eq!(s.replace_all("aba", "$1"), ("$1b$1".to_owned(), 2));
+    }
+
+    fn whole_word_options() -> SearchOptions {
+        SearchOptions {
+            whole_word: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn whole_word_latin() {
+        let s = searcher("cat", whole_word_options());
+        let text = "concat cat_ cats cat.";
+        assert_eq!(s.find_all(text), vec![17..20]);
+        assert_eq!(s.find_next(text, 0), Some(17..20));
+        assert_eq!(s.find_previous(text, text.len()), Some(17..20));
+    }
+
+    #[test]
+    fn whole_word_cjk_script_boundaries() {
+        // Given: 漢字、ひらがな、カタカナが続く文で
+        let s = searcher("東京", whole_word_options());
+        // When: 単語単位で探すと
+        // Then: 文字の種類が変わるところを境界とみなし、漢字が続く「東京都」には一致しない
+        assert_eq!(s.find_all("東京都と東京へ"), vec![12..18]);
+
+        let s = searcher("データ", whole_word_options());
+        assert_eq!(s.find_all("データベースのデータを"), vec![21..30]);
+    }
+
+    #[test]
+    fn whole_word_with_symbols_at_edges() {
+        // 一致の端が記号なら、その側の境界は問わない
+        let s = searcher("-x", whole_word_options());
+        assert_eq!(s.find_all("a-x a-xy"), vec![1..3]);
+    }
+
+    #[test]
+    fn whole_word_replace() {
+        let s = searcher("is", whole_word_options());
+        assert_eq!(
+            s.replace_all("this is his", "IS"),
+            ("this IS his".to_owned(), 1)
+        );
+        assert_eq!(s.replacement_for("this", 2..4, "IS"), None);
+    }
+
+    #[test]
+    fn in_selection_limits_find() {
+        // Given: 2 行目だけを範囲にした検索で
+        let text = "ab\nab\nab";
+        let s = searcher(
+            "ab",
+            SearchOptions {
+                wrap_around: true,
+                ..Default::default()
+            },
+        )
+        .with_scope(3..5);
+        // When/Then: 範囲の外は見つからず、折り返しても範囲の中に戻る
+        assert_eq!(s.find_all(text), vec![3..5]);
+        assert_eq!(s.find_next(text, 0), Some(3..5));
+        assert_eq!(s.find_next(text, 5), Some(3..5));
+        assert_eq!(s.find_previous(text, 3), Some(3..5));
+    }
+
+    #[test]
+    fn in_selection_does_not_match_across_scope_end() {
+        let s = searcher("abc", SearchOptions::default()).with_scope(0..2);
+        assert_eq!(s.find_next("abc", 0), None);
+        // `$` は範囲の末尾に一致する
+        let s = searcher("b$", regex_options()).with_scope(0..2);
+        assert_eq!(s.find_next("abc", 0), Some(1..2));
+    }
+
+    #[test]
+    fn in_selection_replace_all() {
+        let s = searcher("あ", SearchOptions::default()).with_scope(3..9);
+        assert_eq!(
+            s.replace_all("あああああ", "い"),
+            ("あいいああ".to_owned(), 2)
+        );
+        assert_eq!(s.replacement_for("あああああ", 0..3, "い"), None);
+        assert_eq!(
+            s.replacement_for("あああああ", 3..6, "い"),
+            Some("い".to_owned())
+        );
+    }
+
+    #[test]
+    fn stale_scope_is_clamped() {
+        // 範囲が文字の途中やテキストの外を指していても落ちない
+        let s = searcher("い", SearchOptions::default()).with_scope(1..100);
+        assert_eq!(s.find_all("あい"), vec![3..6]);
+    }
+
+    #[test]
+    fn first_from_wraps_to_start() {
+        let matches = [1..2, 5..6, 9..10];
+        assert_eq!(first_from(&matches, 0), Some(0));
+        assert_eq!(first_from(&matches, 5), Some(1));
+        assert_eq!(first_from(&matches, 6), Some(2));
+        assert_eq!(first_from(&matches, 10), Some(0));
+        assert_eq!(first_from(&[], 0), None);
+    }
+
+    #[test]
+    fn nearby_matches_are_capped() {
+        let matches: Vec<_> = (0..10).map(|i| i * 10..i * 10 + 1).collect();
+        // 前後に半分ずつ
+        assert_eq!(nearby(&matches, 50, 4), &matches[3..7]);
+        // 先頭や末尾に近ければ、反対側を多くする
+        assert_eq!(nearby(&matches, 0, 4), &matches[0..4]);
+        assert_eq!(nearby(&matches, 95, 4), &matches[6..10]);
+        assert_eq!(nearby(&matches, 0, 20), &matches[..]);
+    }
+
+    #[test]
+    fn index_of_selected_match() {
+        let matches = [1..2, 5..6, 9..10];
+        assert_eq!(index_of(&matches, &(5..6)), Some(1));
+        assert_eq!(index_of(&matches, &(5..5)), None);
+        assert_eq!(index_of(&matches, &(4..6)), None);
+    }
+}
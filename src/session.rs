@@ -0,0 +1,229 @@
+// 終了時のウィンドウと開いていたファイルを保存し、次の起動時に復元します。
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::settings::{self, Settings};
+
+const FILE_NAME: &str = "session.ini";
+const BACKUP_DIR: &str = "backup";
+
+/// ウィンドウ 1 つ分の状態です。
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct WindowState {
+    pub path: Option<String>,
+    /// ウィンドウの位置と大きさ (x, y, 幅, 高さ)
+    pub rect: Option<(i32, i32, i32, i32)>,
+    pub maximized: bool,
+    /// キャレットの位置 (UTF-8 のバイト位置)
+    pub caret: usize,
+    /// 一番上に表示していた行
+    pub scroll: i32,
+    pub word_wrap: bool,
+    /// 保存していない無題の内容を退避したファイル
+    pub backup: Option<String>,
+}
+
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct Session {
+    pub windows: Vec<WindowState>,
+}
+impl Session {
+    /// 前回のセッションを読み込みます。読めなければ空のセッションを返します。
+    pub fn load() -> Self {
+        session_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = session_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.serialize())
+    }
+
+    /// 設定ファイルと同じ `key=value` 形式で、ウィンドウごとに番号を付けて読み込みます。
+    pub fn parse(text: &str) -> Self {
+        let values = Settings::parse(text);
+        let count = values.get::<usize>("window.count").unwrap_or(0);
+        let windows = (0..count)
+            .map(|i| {
+                let key = |name: &str| format!("window.{}.{}", i, name);
+                WindowState {
+                    path: values.get(&key("path")),
+                    rect: values
+                        .get::<String>(&key("rect"))
+                        .and_then(|value| parse_rect(&value)),
+                    maximized: values.get(&key("maximized")).unwrap_or(false),
+                    caret: values.get(&key("caret")).unwrap_or(0),
+                    scroll: values.get(&key("scroll")).unwrap_or(0),
+                    word_wrap: values.get(&key("word_wrap")).unwrap_or(true),
+                    backup: values.get(&key("backup")),
+                }
+            })
+            // 復元するものがないウィンドウは開きません。
+            .filter(|window| window.path.is_some() || window.backup.is_some())
+            .collect();
+        Self { windows }
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut values = Settings::default();
+        values.set("window.count", self.windows.len());
+        for (i, window) in self.windows.iter().enumerate() {
+            let key = |name: &str| format!("window.{}.{}", i, name);
+            if let Some(path) = &window.path {
+                values.set(&key("path"), path);
+            }
+            if let Some((x, y, width, height)) = window.rect {
+                values.set(&key("rect"), format!("{},{},{},{}", x, y, width, height));
+            }
+            values.set(&key("maximized"), window.maximized);
+            values.set(&key("caret"), window.caret);
+            values.set(&key("scroll"), window.scroll);
+            values.set(&key("word_wrap"), window.word_wrap);
+            if let Some(backup) = &window.backup {
+                values.set(&key("backup"), backup);
+            }
+        }
+        values.serialize()
+    }
+}
+
+fn parse_rect(value: &str) -> Option<(i32, i32, i32, i32)> {
+    let numbers: Vec<i32> = value
+        .split(',')
+        .map(|n| n.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers[..] {
+        [x, y, width, height] if width > 0 && height > 0 => Some((x, y, width, height)),
+        _ => None,
+    }
+}
+
+fn session_path() -> Option<PathBuf> {
+    settings::config_dir().map(|dir| dir.join(FILE_NAME))
+}
+
+/// 保存していない無題の内容を退避し、退避先のパスを返します。
+/// 前回の退避先 `previous` がなければ、他のウィンドウと重ならない名前で新しく作ります。
+pub fn write_backup(previous: Option<&str>, text: &str) -> io::Result<String> {
+    let path = match previous {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let dir = settings::config_dir()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?
+                .join(BACKUP_DIR);
+            fs::create_dir_all(&dir)?;
+            create_backup_file(&dir)?
+        }
+    };
+    fs::write(&path, text)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// `dir` に空の退避ファイルを作り、そのパスを返します。
+/// 同時に閉じたウィンドウが同じファイルに書かないよう、既にある番号は使いません。
+fn create_backup_file(dir: &Path) -> io::Result<PathBuf> {
+    let mut index = 0;
+    loop {
+        let path = dir.join(format!("untitled-{}.txt", index));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => index += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// 復元し終えた退避ファイルを消します。
+pub fn remove_backup(path: &str) {
+    // 消せなくても次回上書きするだけなので、エラーは無視します。
+    _ = fs::remove_file(path);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn round_trip() {
+        // Given: ファイルと無題の 2 つのウィンドウを
+        let session = Session {
+            windows: vec![
+                WindowState {
+                    path: Some("C:\\memo\\今日.txt".to_owned()),
+                    rect: Some((-8, 10, 800, 600)),
+                    maximized: false,
+                    caret: 42,
+                    scroll: 3,
+                    word_wrap: false,
+                    backup: None,
+                },
+                WindowState {
+                    path: None,
+                    rect: None,
+                    maximized: true,
+                    caret: 0,
+                    scroll: 0,
+                    word_wrap: true,
+                    backup: Some("/tmp/untitled-1.txt".to_owned()),
+                },
+            ],
+        };
+        // When: 書き出して読み直すと
+        let restored = Session::parse(&session.serialize());
+        // Then: 同じ状態が得られる
+        assert_eq!(restored, session);
+    }
+
+    #[test]
+    fn missing_values_use_defaults() {
+        let session = Session::parse("window.count=1\nwindow.0.path=a.txt\n");
+        assert_eq!(
+            session.windows,
+            vec![WindowState {
+                path: Some("a.txt".to_owned()),
+                word_wrap: true,
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn broken_values_are_ignored() {
+        let session = Session::parse(
+            "window.count=2\n\
+             window.0.path=a.txt\nwindow.0.rect=1,2,0,4\nwindow.0.caret=x\n\
+             window.1.caret=10\n",
+        );
+        // 大きさが 0 の位置は使わず、ファイルも退避もないウィンドウは開かない
+        assert_eq!(session.windows.len(), 1);
+        assert_eq!(session.windows[0].rect, None);
+        assert_eq!(session.windows[0].caret, 0);
+    }
+
+    #[test]
+    fn backup_files_do_not_collide() {
+        // Given: 退避先のディレクトリに
+        let dir = env::temp_dir().join(format!("claw_editor_{}_backup", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // When: 2 つのウィンドウが続けて退避すると
+        let first = create_backup_file(&dir).unwrap();
+        let second = create_backup_file(&dir).unwrap();
+        // Then: 別のファイルになる
+        assert_ne!(first, second);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
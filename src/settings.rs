@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const APP_DIR: &str = "ClawEditor";
+const FILE_NAME: &str = "settings.ini";
+
+// 設定のキー
+pub const ZOOM: &str = "view.zoom";
+pub const LINE_NUMBERS: &str = "view.line_numbers";
+pub const MARKDOWN_PREVIEW: &str = "view.markdown_preview";
+pub const LARGE_FILE_THRESHOLD_MB: &str = "file.large_file_threshold_mb";
+pub const PREVIEW_MB: &str = "file.preview_mb";
+pub const HOT_EXIT: &str = "session.hot_exit";
+pub const TRIM_TRAILING_WHITESPACE: &str = "save.trim_trailing_whitespace";
+pub const TRIM_FULL_WIDTH_SPACE: &str = "save.trim_full_width_space";
+pub const FINAL_NEWLINE: &str = "save.final_newline";
+/// 編集コントロールの種類 ("textctrl" または "scintilla")。再起動すると反映します。
+pub const EDITOR_BACKEND: &str = "editor.backend";
+pub const TAB_WIDTH: &str = "editor.tab_width";
+pub const INDENT_WITH_SPACES: &str = "editor.indent_with_spaces";
+pub const SORT_IGNORE_CASE: &str = "sort.ignore_case";
+pub const SORT_NUMERIC: &str = "sort.numeric";
+
+/// `key=value` 形式の行で保存する設定です。
+#[derive(Default)]
+pub struct Settings {
+    values: BTreeMap<String, String>,
+}
+impl Settings {
+    /// 設定ファイルを読み込みます。読めなければ空の設定を返します。
+    pub fn load() -> Self {
+        config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| Self::parse(&text))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = config_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.serialize())
+    }
+
+    /// 設定を読み込んで `f` で変更し、保存します。
+    pub fn update<F: FnOnce(&mut Settings)>(f: F) {
+        let mut settings = Self::load();
+        f(&mut settings);
+        // 設定を保存できなくても編集は続けられるので、エラーは無視します。
+        _ = settings.save();
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let values = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.trim().to_owned(), unescape(value.trim())))
+            .collect();
+        Self { values }
+    }
+
+    pub fn serialize(&self) -> String {
+        self.values
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, escape(value)))
+            .collect()
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.values.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn set<T: ToString>(&mut self, key: &str, value: T) {
+        self.values.insert(key.to_owned(), value.to_string());
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// 設定ファイルを置くディレクトリを返します。
+pub fn config_dir() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join(APP_DIR))
+}
+
+fn config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(FILE_NAME))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_ignores_comments_and_blank_lines() {
+        let settings = Settings::parse("# comment\n\nview.zoom = 150\nbroken line\n");
+        assert_eq!(settings.get::<i32>(ZOOM), Some(150));
+        assert_eq!(settings.values.len(), 1);
+    }
+
+    #[test]
+    fn get_returns_none_for_invalid_value() {
+        let settings = Settings::parse("view.zoom=abc");
+        assert_eq!(settings.get::<i32>(ZOOM), None);
+        assert_eq!(settings.get::<String>(ZOOM), Some("abc".to_owned()));
+    }
+
+    #[test]
+    fn round_trip() {
+        // Given: 改行やバックスラッシュを含む値を設定して
+        let mut settings = Settings::default();
+        settings.set(ZOOM, 120);
+        settings.set("some.path", "C:\\memo\\今日.txt\nnext");
+        // When: 書き出して読み直すと
+        let restored = Settings::parse(&settings.serialize());
+        // Then: 同じ値が得られる
+        assert_eq!(restored.get::<i32>(ZOOM), Some(120));
+        assert_eq!(
+            restored.get::<String>("some.path"),
+            Some("C:\\memo\\今日.txt\nnext".to_owned())
+        );
+    }
+}
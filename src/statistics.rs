@@ -0,0 +1,262 @@
+use wx;
+use wx::methods::*;
+
+use crate::encoding::{self, TextFormat};
+use crate::search::word_class;
+
+/// 文書や選択範囲の文字数などを数えた結果です。改行は文字として数えません。
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Statistics {
+    pub chars: usize,
+    pub chars_without_whitespace: usize,
+    pub full_width: usize,
+    pub half_width: usize,
+    pub words: usize,
+    pub lines: usize,
+    pub paragraphs: usize,
+    /// 今の文字コードで保存したときのバイト数。`of` では数えません。
+    pub bytes: usize,
+}
+impl Statistics {
+    pub fn of(text: &str) -> Self {
+        let mut stats = Self::default();
+        // 日本語は空白で区切らないため、文字の種類が変わるところで単語を区切ります。
+        let mut previous_class = None;
+        for c in text.chars() {
+            if c == '\n' || c == '\r' {
+                previous_class = None;
+                continue;
+            }
+            stats.chars += 1;
+            if !c.is_whitespace() {
+                stats.chars_without_whitespace += 1;
+            }
+            if is_full_width(c) {
+                stats.full_width += 1;
+            } else {
+                stats.half_width += 1;
+            }
+            let class = word_class(c);
+            if class.is_some() && class != previous_class {
+                stats.words += 1;
+            }
+            previous_class = class;
+        }
+        let mut in_paragraph = false;
+        for line in text.split('\n') {
+            stats.lines += 1;
+            let blank = line.trim().is_empty();
+            if !blank && !in_paragraph {
+                stats.paragraphs += 1;
+            }
+            in_paragraph = !blank;
+        }
+        stats
+    }
+
+    /// 文書全体を数えます。バイト数には BOM も含めます。
+    pub fn of_document(text: &str, format: TextFormat) -> Self {
+        Self {
+            bytes: encoding::encode(text, format).0.len(),
+            ..Self::of(text)
+        }
+    }
+
+    /// 選択範囲を数えます。BOM はファイルの先頭にしか付かないので、バイト数に含めません。
+    pub fn of_selection(text: &str, format: TextFormat) -> Self {
+        Self::of_document(
+            text,
+            TextFormat {
+                bom: false,
+                ..format
+            },
+        )
+    }
+}
+
+/// 文書全体と選択範囲の統計をダイアログに表示します。
+pub fn show<W: WindowMethods>(
+    parent: &W,
+    encoding: &str,
+    whole: &Statistics,
+    selection: Option<&Statistics>,
+) {
+    let dialog = wx::Dialog::builder(Some(parent))
+        .title("文書の統計")
+        .build();
+    let rows: [(&str, fn(&Statistics) -> usize); 8] = [
+        ("文字数 (空白を含む)", |s| s.chars),
+        ("文字数 (空白を除く)", |s| {
+            s.chars_without_whitespace
+        }),
+        ("全角文字", |s| s.full_width),
+        ("半角文字", |s| s.half_width),
+        ("単語数", |s| s.words),
+        ("行数", |s| s.lines),
+        ("段落数", |s| s.paragraphs),
+        ("バイト数", |s| s.bytes),
+    ];
+    let grid = wx::FlexGridSizer::new_with_int(3, 6, 24);
+    let add = |label: &str, flag| {
+        let text = wx::StaticText::builder(Some(&dialog)).label(label).build();
+        grid.add_window_int(Some(&text), 0, flag, 0, wx::Object::none());
+    };
+    add("", wx::ALIGN_LEFT);
+    add("文書全体", wx::ALIGN_RIGHT);
+    add("選択範囲", wx::ALIGN_RIGHT);
+    for (label, value) in rows {
+        add(label, wx::ALIGN_LEFT);
+        add(&thousands(value(whole)), wx::ALIGN_RIGHT);
+        add(
+            &selection.map_or("-".to_owned(), |s| thousands(value(s))),
+            wx::ALIGN_RIGHT,
+        );
+    }
+    let note = wx::StaticText::builder(Some(&dialog))
+        .label(&format!(
+            "バイト数は {} で保存したときの大きさです。\n\
+             日本語の単語は、漢字やひらがななど文字の種類が変わるところで区切って数えます。",
+            encoding
+        ))
+        .build();
+
+    let sizer = wx::BoxSizer::new(wx::VERTICAL);
+    sizer.add_sizer_int(Some(&grid), 0, wx::ALL, 12, wx::Object::none());
+    sizer.add_window_int(
+        Some(&note),
+        0,
+        wx::LEFT | wx::RIGHT | wx::BOTTOM,
+        12,
+        wx::Object::none(),
+    );
+    let buttons = dialog.create_std_dialog_button_sizer(wx::OK);
+    sizer.add_sizer_int(
+        Some(&buttons),
+        0,
+        wx::EXPAND | wx::ALL,
+        8,
+        wx::Object::none(),
+    );
+    dialog.set_sizer_and_fit(Some(&sizer), true);
+    dialog.centre(wx::BOTH);
+    dialog.show_modal();
+}
+
+/// 東アジアの文字幅が全角 (Wide または Fullwidth) の文字なら真を返します。
+pub fn is_full_width(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{20000}'..='\u{3FFFD}')
+}
+
+/// 数を 3 桁ごとにカンマで区切ります。
+pub fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut end = match digits.len() % 3 {
+        0 => 3,
+        head => head,
+    };
+    let mut result = digits[..end].to_owned();
+    while end < digits.len() {
+        result.push(',');
+        result.push_str(&digits[end..end + 3]);
+        end += 3;
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_text() {
+        let stats = Statistics::of("");
+        assert_eq!(stats.chars, 0);
+        assert_eq!(stats.words, 0);
+        assert_eq!(stats.lines, 1);
+        assert_eq!(stats.paragraphs, 0);
+    }
+
+    #[test]
+    fn latin_text() {
+        let stats = Statistics::of("Hello, world!\r\nfoo_bar 42");
+        assert_eq!(stats.chars, 23);
+        assert_eq!(stats.chars_without_whitespace, 21);
+        assert_eq!(stats.half_width, 23);
+        assert_eq!(stats.full_width, 0);
+        assert_eq!(stats.words, 4);
+        assert_eq!(stats.lines, 2);
+        assert_eq!(stats.paragraphs, 1);
+    }
+
+    #[test]
+    fn japanese_text() {
+        // Given: 空白で区切らない日本語の文を
+        let stats = Statistics::of("東京へ行きました。　データベース");
+        // When/Then: 文字の種類が変わるところで単語を区切って数える
+        // (東京 / へ / 行 / きました / データベース)
+        assert_eq!(stats.words, 5);
+        assert_eq!(stats.chars, 16);
+        // 全角の空白は空白として扱う
+        assert_eq!(stats.chars_without_whitespace, 15);
+        assert_eq!(stats.full_width, 16);
+        assert_eq!(stats.half_width, 0);
+    }
+
+    #[test]
+    fn mixed_width() {
+        let stats = Statistics::of("ABCＡＢＣｱｲｳ");
+        // 半角カタカナは半角
+        assert_eq!(stats.full_width, 3);
+        assert_eq!(stats.half_width, 6);
+        // 全角英字も英数字の単語としてつながる
+        assert_eq!(stats.words, 2);
+    }
+
+    #[test]
+    fn katakana_middle_dot_separates_words() {
+        // 中点はカタカナの単語に含めない
+        let stats = Statistics::of("コーヒー・ブレイク");
+        assert_eq!(stats.words, 2);
+    }
+
+    #[test]
+    fn bom_is_counted_only_for_whole_document() {
+        // Given: BOM 付き UTF-8 の文書を
+        let format = TextFormat {
+            bom: true,
+            ..TextFormat::default()
+        };
+        // When/Then: 文書全体は BOM の 3 バイトを含み、選択範囲は含まない
+        assert_eq!(Statistics::of_document("abc", format).bytes, 6);
+        assert_eq!(Statistics::of_selection("abc", format).bytes, 3);
+    }
+
+    #[test]
+    fn paragraphs_are_separated_by_blank_lines() {
+        let stats = Statistics::of("一段落目\n続き\n\n  \n二段落目\n");
+        assert_eq!(stats.lines, 6);
+        assert_eq!(stats.paragraphs, 2);
+    }
+
+    #[test]
+    fn thousands_separator() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1000), "1,000");
+        assert_eq!(thousands(1234567), "1,234,567");
+    }
+}
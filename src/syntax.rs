@@ -0,0 +1,311 @@
+// ファイルの種類ごとの色分けの定義です。Scintilla の字句解析器と、
+// そのスタイル番号をどの字句として色を付けるかを表にしています。
+// 言語を増やすときは `LANGUAGES` に定義を足すだけで、メニューにも現れます。
+
+use std::os::raw::c_int;
+use std::path::Path;
+
+/// 色を分ける字句の種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Token {
+    Comment,
+    Keyword,
+    Type,
+    String,
+    Number,
+    Operator,
+    Macro,
+    Heading,
+    Emphasis,
+    Link,
+    Code,
+    Added,
+    Deleted,
+}
+impl Token {
+    pub fn colour(self) -> &'static str {
+        match self {
+            Token::Comment => "#008000",
+            Token::Keyword => "#0000C0",
+            Token::Type => "#2B7A8C",
+            Token::String => "#A31515",
+            Token::Number => "#098658",
+            Token::Operator => "#505050",
+            Token::Macro => "#8A2BE2",
+            Token::Heading => "#0000C0",
+            Token::Emphasis => "#000000",
+            Token::Link => "#0066CC",
+            Token::Code => "#A31515",
+            Token::Added => "#008000",
+            Token::Deleted => "#C00000",
+        }
+    }
+
+    pub fn is_bold(self) -> bool {
+        matches!(self, Token::Keyword | Token::Heading | Token::Emphasis)
+    }
+}
+
+pub struct Language {
+    /// メニューに表示する名前
+    pub label: &'static str,
+    /// 小文字の拡張子、またはファイル名 ("cargo.lock" など)
+    pub patterns: &'static [&'static str],
+    /// Scintilla の字句解析器
+    pub lexer: c_int,
+    /// 字句解析器に渡すキーワードの組。空白で区切ります。
+    pub keywords: &'static [&'static str],
+    /// Scintilla のスタイル番号と、その字句の種類
+    pub styles: &'static [(c_int, Token)],
+}
+impl PartialEq for Language {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// 色分けに使う言語です。ユーザーが選ばなければ、ファイル名から決めます。
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Choice {
+    #[default]
+    Auto,
+    PlainText,
+    Fixed(&'static Language),
+}
+impl Choice {
+    pub fn resolve(self, path: Option<&str>) -> Option<&'static Language> {
+        match self {
+            Choice::Auto => path.and_then(detect),
+            Choice::PlainText => None,
+            Choice::Fixed(language) => Some(language),
+        }
+    }
+}
+
+/// ファイル名から言語を決めます。
+pub fn detect(path: &str) -> Option<&'static Language> {
+    let name = Path::new(path).file_name()?.to_str()?.to_lowercase();
+    LANGUAGES.iter().find(|language| {
+        language.patterns.iter().any(|pattern| {
+            name == *pattern
+                || name
+                    .strip_suffix(pattern)
+                    .is_some_and(|stem| stem.ends_with('.'))
+        })
+    })
+}
+
+pub static LANGUAGES: &[Language] = &[
+    Language {
+        label: "Rust",
+        patterns: &["rs"],
+        lexer: wx::STC_LEX_RUST,
+        keywords: &[
+            "as async await break const continue crate dyn else enum extern false fn for \
+             if impl in let loop match mod move mut pub ref return self Self static struct \
+             super trait true type unsafe use where while",
+            "bool char f32 f64 i8 i16 i32 i64 i128 isize str u8 u16 u32 u64 u128 usize \
+             String Vec Option Result Box Rc Arc Some None Ok Err",
+        ],
+        styles: &[
+            (wx::STC_RUST_COMMENTBLOCK, Token::Comment),
+            (wx::STC_RUST_COMMENTLINE, Token::Comment),
+            (wx::STC_RUST_COMMENTBLOCKDOC, Token::Comment),
+            (wx::STC_RUST_COMMENTLINEDOC, Token::Comment),
+            (wx::STC_RUST_NUMBER, Token::Number),
+            (wx::STC_RUST_WORD, Token::Keyword),
+            (wx::STC_RUST_WORD2, Token::Type),
+            (wx::STC_RUST_STRING, Token::String),
+            (wx::STC_RUST_STRINGR, Token::String),
+            (wx::STC_RUST_CHARACTER, Token::String),
+            (wx::STC_RUST_BYTESTRING, Token::String),
+            (wx::STC_RUST_BYTESTRINGR, Token::String),
+            (wx::STC_RUST_BYTECHARACTER, Token::String),
+            (wx::STC_RUST_OPERATOR, Token::Operator),
+            (wx::STC_RUST_LIFETIME, Token::Type),
+            (wx::STC_RUST_MACRO, Token::Macro),
+        ],
+    },
+    Language {
+        label: "TOML",
+        patterns: &["toml", "cargo.lock"],
+        // Scintilla に TOML の字句解析器がないため、書式の近い INI のものを使います。
+        lexer: wx::STC_LEX_PROPERTIES,
+        keywords: &[],
+        styles: &[
+            (wx::STC_PROPS_COMMENT, Token::Comment),
+            (wx::STC_PROPS_SECTION, Token::Heading),
+            (wx::STC_PROPS_ASSIGNMENT, Token::Operator),
+            (wx::STC_PROPS_KEY, Token::Keyword),
+        ],
+    },
+    Language {
+        label: "Markdown",
+        patterns: &["md", "markdown", "mkd"],
+        lexer: wx::STC_LEX_MARKDOWN,
+        keywords: &[],
+        styles: &[
+            (wx::STC_MARKDOWN_STRONG1, Token::Emphasis),
+            (wx::STC_MARKDOWN_STRONG2, Token::Emphasis),
+            (wx::STC_MARKDOWN_EM1, Token::Emphasis),
+            (wx::STC_MARKDOWN_EM2, Token::Emphasis),
+            (wx::STC_MARKDOWN_HEADER1, Token::Heading),
+            (wx::STC_MARKDOWN_HEADER2, Token::Heading),
+            (wx::STC_MARKDOWN_HEADER3, Token::Heading),
+            (wx::STC_MARKDOWN_HEADER4, Token::Heading),
+            (wx::STC_MARKDOWN_HEADER5, Token::Heading),
+            (wx::STC_MARKDOWN_HEADER6, Token::Heading),
+            (wx::STC_MARKDOWN_ULIST_ITEM, Token::Keyword),
+            (wx::STC_MARKDOWN_OLIST_ITEM, Token::Keyword),
+            (wx::STC_MARKDOWN_BLOCKQUOTE, Token::Comment),
+            (wx::STC_MARKDOWN_HRULE, Token::Operator),
+            (wx::STC_MARKDOWN_LINK, Token::Link),
+            (wx::STC_MARKDOWN_CODE, Token::Code),
+            (wx::STC_MARKDOWN_CODE2, Token::Code),
+            (wx::STC_MARKDOWN_CODEBK, Token::Code),
+        ],
+    },
+    Language {
+        label: "JSON",
+        patterns: &["json"],
+        lexer: wx::STC_LEX_JSON,
+        keywords: &["true false null"],
+        styles: &[
+            (wx::STC_JSON_NUMBER, Token::Number),
+            (wx::STC_JSON_STRING, Token::String),
+            (wx::STC_JSON_STRINGEOL, Token::String),
+            (wx::STC_JSON_PROPERTYNAME, Token::Type),
+            (wx::STC_JSON_ESCAPESEQUENCE, Token::Macro),
+            (wx::STC_JSON_LINECOMMENT, Token::Comment),
+            (wx::STC_JSON_BLOCKCOMMENT, Token::Comment),
+            (wx::STC_JSON_OPERATOR, Token::Operator),
+            (wx::STC_JSON_KEYWORD, Token::Keyword),
+        ],
+    },
+    Language {
+        label: "INI",
+        patterns: &["ini", "cfg", "conf", "properties"],
+        lexer: wx::STC_LEX_PROPERTIES,
+        keywords: &[],
+        styles: &[
+            (wx::STC_PROPS_COMMENT, Token::Comment),
+            (wx::STC_PROPS_SECTION, Token::Heading),
+            (wx::STC_PROPS_ASSIGNMENT, Token::Operator),
+            (wx::STC_PROPS_DEFVAL, Token::Macro),
+            (wx::STC_PROPS_KEY, Token::Keyword),
+        ],
+    },
+    Language {
+        label: "シェルスクリプト",
+        patterns: &[
+            "sh",
+            "bash",
+            "zsh",
+            ".bashrc",
+            ".bash_profile",
+            ".profile",
+            ".zshrc",
+        ],
+        lexer: wx::STC_LEX_BASH,
+        keywords: &[
+            "case do done elif else esac exit export fi for function if in local read \
+             readonly return select set shift source then unset until while",
+        ],
+        styles: &[
+            (wx::STC_SH_COMMENTLINE, Token::Comment),
+            (wx::STC_SH_NUMBER, Token::Number),
+            (wx::STC_SH_WORD, Token::Keyword),
+            (wx::STC_SH_STRING, Token::String),
+            (wx::STC_SH_CHARACTER, Token::String),
+            (wx::STC_SH_OPERATOR, Token::Operator),
+            (wx::STC_SH_SCALAR, Token::Type),
+            (wx::STC_SH_PARAM, Token::Type),
+            (wx::STC_SH_BACKTICKS, Token::Macro),
+            (wx::STC_SH_HERE_DELIM, Token::String),
+            (wx::STC_SH_HERE_Q, Token::String),
+        ],
+    },
+    Language {
+        label: "Python",
+        patterns: &["py", "pyw"],
+        lexer: wx::STC_LEX_PYTHON,
+        keywords: &[
+            "False None True and as assert async await break class continue def del elif \
+             else except finally for from global if import in is lambda nonlocal not or \
+             pass raise return try while with yield",
+        ],
+        styles: &[
+            (wx::STC_P_COMMENTLINE, Token::Comment),
+            (wx::STC_P_COMMENTBLOCK, Token::Comment),
+            (wx::STC_P_NUMBER, Token::Number),
+            (wx::STC_P_STRING, Token::String),
+            (wx::STC_P_CHARACTER, Token::String),
+            (wx::STC_P_TRIPLE, Token::String),
+            (wx::STC_P_TRIPLEDOUBLE, Token::String),
+            (wx::STC_P_STRINGEOL, Token::String),
+            (wx::STC_P_WORD, Token::Keyword),
+            (wx::STC_P_CLASSNAME, Token::Type),
+            (wx::STC_P_DEFNAME, Token::Type),
+            (wx::STC_P_OPERATOR, Token::Operator),
+            (wx::STC_P_DECORATOR, Token::Macro),
+        ],
+    },
+    Language {
+        label: "差分 (diff)",
+        patterns: &["diff", "patch"],
+        lexer: wx::STC_LEX_DIFF,
+        keywords: &[],
+        styles: &[
+            (wx::STC_DIFF_COMMENT, Token::Comment),
+            (wx::STC_DIFF_COMMAND, Token::Keyword),
+            (wx::STC_DIFF_HEADER, Token::Heading),
+            (wx::STC_DIFF_POSITION, Token::Macro),
+            (wx::STC_DIFF_DELETED, Token::Deleted),
+            (wx::STC_DIFF_ADDED, Token::Added),
+            (wx::STC_DIFF_CHANGED, Token::Type),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn label(path: &str) -> Option<&'static str> {
+        detect(path).map(|language| language.label)
+    }
+
+    #[test]
+    fn detect_by_extension() {
+        assert_eq!(label("src/main.rs"), Some("Rust"));
+        assert_eq!(label("C:\\memo\\README.MD"), Some("Markdown"));
+        assert_eq!(label("fix.patch"), Some("差分 (diff)"));
+        assert_eq!(label("memo.txt"), None);
+        // 拡張子の一部だけが一致しても、その言語にはしない
+        assert_eq!(label("archive.crs"), None);
+    }
+
+    #[test]
+    fn detect_by_file_name() {
+        assert_eq!(label("Cargo.lock"), Some("TOML"));
+        assert_eq!(label("/home/user/.bashrc"), Some("シェルスクリプト"));
+        // 拡張子のないファイルは色分けしない
+        assert_eq!(label("Makefile"), None);
+    }
+
+    #[test]
+    fn choice_overrides_detection() {
+        // Given: Markdown のファイルでも
+        let path = Some("notes.md");
+        // When/Then: 選んだ言語が優先される
+        assert_eq!(
+            Choice::Auto.resolve(path).map(|l| l.label),
+            Some("Markdown")
+        );
+        assert!(Choice::PlainText.resolve(path).is_none());
+        let python = LANGUAGES.iter().find(|l| l.label == "Python").unwrap();
+        assert!(Choice::Fixed(python).resolve(path) == Some(python));
+        // 無題の文書は色分けしない
+        assert!(Choice::Auto.resolve(None).is_none());
+    }
+}
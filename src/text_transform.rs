@@ -0,0 +1,583 @@
+// 保存時の空白の整理やインデントの変換など、テキストを書き換える処理です。
+
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::encoding::LineEnding;
+
+const FULL_WIDTH_SPACE: char = '\u{3000}';
+
+/// 保存するときに末尾の改行をどうするか
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum FinalNewline {
+    #[default]
+    Keep,
+    /// 改行で終わっていなければ追加します。
+    Ensure,
+    /// 末尾の改行を 1 つ削除します。空行で終わっていれば空行は残します。
+    Remove,
+}
+impl FromStr for FinalNewline {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(FinalNewline::Keep),
+            "ensure" => Ok(FinalNewline::Ensure),
+            "remove" => Ok(FinalNewline::Remove),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for FinalNewline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FinalNewline::Keep => "keep",
+            FinalNewline::Ensure => "ensure",
+            FinalNewline::Remove => "remove",
+        })
+    }
+}
+
+/// 保存するときの空白の整理方法。文書ごとに持ちます。
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Cleanup {
+    pub trim_trailing_whitespace: bool,
+    /// 行末の全角空白 (U+3000) も削除します。
+    pub trim_full_width_space: bool,
+    pub final_newline: FinalNewline,
+}
+impl Cleanup {
+    pub fn apply(&self, text: &str) -> String {
+        let text = if self.trim_trailing_whitespace {
+            trim_trailing_whitespace(text, self.trim_full_width_space)
+        } else {
+            text.to_owned()
+        };
+        set_final_newline(&text, self.final_newline)
+    }
+}
+
+fn is_trailing_space(c: char, full_width: bool) -> bool {
+    c == ' ' || c == '\t' || (full_width && c == FULL_WIDTH_SPACE)
+}
+
+/// 各行の末尾の空白とタブを削除します。
+/// `full_width` が真なら全角空白も削除します。
+pub fn trim_trailing_whitespace(text: &str, full_width: bool) -> String {
+    text.split('\n')
+        .map(|line| {
+            let (line, cr) = match line.strip_suffix('\r') {
+                Some(line) => (line, "\r"),
+                None => (line, ""),
+            };
+            let trimmed = line.trim_end_matches(|c| is_trailing_space(c, full_width));
+            format!("{}{}", trimmed, cr)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn set_final_newline(text: &str, final_newline: FinalNewline) -> String {
+    match final_newline {
+        FinalNewline::Keep => text.to_owned(),
+        // 追加する改行は、文書で使っている改行コードに合わせます。
+        FinalNewline::Ensure if !text.is_empty() && !text.ends_with('\n') => {
+            format!("{}{}", text, LineEnding::detect(text).as_str())
+        }
+        FinalNewline::Ensure => text.to_owned(),
+        FinalNewline::Remove => {
+            let text = text.strip_suffix('\n').unwrap_or(text);
+            text.strip_suffix('\r').unwrap_or(text).to_owned()
+        }
+    }
+}
+
+/// 書き換える前のキャレットの位置を、書き換えた後のテキストの位置に移します。
+/// 行と桁を保ち、行が短くなっていれば行末に、行がなくなっていれば最後の行に置きます。
+pub fn map_caret(old_text: &str, caret: usize, new_text: &str) -> usize {
+    let before = &old_text[..caret.min(old_text.len())];
+    let line = before.matches('\n').count();
+    let mut column = before.rsplit('\n').next().unwrap_or("").chars().count();
+
+    let mut line_start = 0;
+    for _ in 0..line {
+        match new_text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => {
+                // 行がなくなったので、最後の行の末尾に置きます。
+                column = usize::MAX;
+                break;
+            }
+        }
+    }
+    let rest = &new_text[line_start..];
+    let line_text = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let line_text = line_text.strip_suffix('\r').unwrap_or(line_text);
+    line_start
+        + line_text
+            .char_indices()
+            .nth(column)
+            .map_or(line_text.len(), |(i, _)| i)
+}
+
+/// `range` を含む行全体の範囲を、最後の改行を含めずに返します。
+/// 選択範囲が行頭で終わっていれば、その行は含めません。
+pub fn line_range(text: &str, range: Range<usize>) -> Range<usize> {
+    let start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = if range.end > range.start && text[..range.end].ends_with('\n') {
+        range.end - 1
+    } else {
+        text[range.end..]
+            .find('\n')
+            .map_or(text.len(), |i| range.end + i)
+    };
+    start..end
+}
+
+/// 各行の先頭の空白とタブを、インデントの幅 (桁数) と残りに分けます。
+fn split_indent(line: &str, tab_width: usize) -> (usize, &str) {
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            ' ' => column += 1,
+            '\t' => column = (column / tab_width + 1) * tab_width,
+            _ => return (column, &line[i..]),
+        }
+    }
+    (column, "")
+}
+
+fn map_indent<F: Fn(usize) -> String>(text: &str, tab_width: usize, indent: F) -> String {
+    let tab_width = tab_width.max(1);
+    text.split('\n')
+        .map(|line| {
+            let (column, rest) = split_indent(line, tab_width);
+            format!("{}{}", indent(column), rest)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 行頭のタブを、同じ幅の空白に変換します。
+pub fn tabs_to_spaces(text: &str, tab_width: usize) -> String {
+    map_indent(text, tab_width, |column| " ".repeat(column))
+}
+
+/// 行頭の空白を、できるだけタブに変換します。タブに満たない分は空白のまま残します。
+pub fn spaces_to_tabs(text: &str, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
+    map_indent(text, tab_width, |column| {
+        format!(
+            "{}{}",
+            "\t".repeat(column / tab_width),
+            " ".repeat(column % tab_width)
+        )
+    })
+}
+
+/// 文書で使われているインデントの種類
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Indentation {
+    /// インデントされた行がありません。
+    Unknown,
+    Tabs,
+    /// 空白と、推定したインデントの幅
+    Spaces(usize),
+    /// タブの行と空白の行が混在しています。
+    Mixed,
+}
+impl fmt::Display for Indentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indentation::Unknown => f.write_str("-"),
+            Indentation::Tabs => f.write_str("タブ"),
+            Indentation::Spaces(width) => write!(f, "空白 {}", width),
+            Indentation::Mixed => f.write_str("タブと空白"),
+        }
+    }
+}
+
+/// 行頭の空白とタブから、インデントの種類を推定します。
+/// 空白 1 つだけの字下げは、位置合わせとみなして数えません。
+pub fn detect_indentation(text: &str) -> Indentation {
+    let mut tab_lines = 0;
+    let mut space_widths = Vec::new();
+    for line in text.lines() {
+        if line.starts_with('\t') {
+            tab_lines += 1;
+        } else {
+            let width = line.len() - line.trim_start_matches(' ').len();
+            // 空白だけの行も数えません。
+            if width >= 2 && width < line.len() && !line[width..].starts_with('\t') {
+                space_widths.push(width);
+            }
+        }
+    }
+    match (tab_lines, space_widths.len()) {
+        (0, 0) => Indentation::Unknown,
+        (_, 0) => Indentation::Tabs,
+        (0, _) => {
+            let width = space_widths.iter().copied().fold(0, gcd);
+            if width >= 2 {
+                Indentation::Spaces(width)
+            } else {
+                Indentation::Spaces(space_widths.iter().copied().min().unwrap_or(width))
+            }
+        }
+        _ => Indentation::Mixed,
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// 単語の先頭の英字を大文字に、残りを小文字にします。
+/// 「don't」のような語中のアポストロフィでは単語を区切りません。
+pub fn to_title_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_word = false;
+    for c in text.chars() {
+        if in_word {
+            result.extend(c.to_lowercase());
+        } else {
+            result.extend(c.to_uppercase());
+        }
+        in_word = c.is_alphanumeric() || (in_word && c == '\'');
+    }
+    result
+}
+
+// 全角英数字と半角英数字のコードポイントの差
+const FULL_WIDTH_OFFSET: u32 = 0xFEE0;
+
+/// 半角の英数字を全角にします。記号や空白はそのまま残します。
+pub fn to_full_width_alphanumeric(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                char::from_u32(c as u32 + FULL_WIDTH_OFFSET).unwrap_or(c)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// 全角の英数字を半角にします。
+pub fn to_half_width_alphanumeric(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+                char::from_u32(c as u32 - FULL_WIDTH_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+// 半角カタカナ (U+FF66〜U+FF9F) と、対応する全角の文字
+// 句読点やかぎ括弧、中点はカタカナではないので変えません。
+const HALF_WIDTH_KATAKANA: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝﾞﾟ";
+const FULL_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+const HALF_WIDTH_VOICED_MARK: char = 'ﾞ';
+const HALF_WIDTH_SEMI_VOICED_MARK: char = 'ﾟ';
+
+/// 濁点を付けた全角カタカナを返します。
+fn voiced(c: char) -> Option<char> {
+    match c {
+        'カ' | 'キ' | 'ク' | 'ケ' | 'コ' | 'サ' | 'シ' | 'ス' | 'セ' | 'ソ' | 'タ' | 'チ'
+        | 'ツ' | 'テ' | 'ト' | 'ハ' | 'ヒ' | 'フ' | 'ヘ' | 'ホ' => {
+            char::from_u32(c as u32 + 1)
+        }
+        'ウ' => Some('ヴ'),
+        _ => None,
+    }
+}
+
+/// 半濁点を付けた全角カタカナを返します。
+fn semi_voiced(c: char) -> Option<char> {
+    match c {
+        'ハ' | 'ヒ' | 'フ' | 'ヘ' | 'ホ' => char::from_u32(c as u32 + 2),
+        _ => None,
+    }
+}
+
+/// 半角カタカナを全角にします。後ろに続く濁点と半濁点は 1 文字にまとめます。
+pub fn to_full_width_katakana(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let Some(index) = HALF_WIDTH_KATAKANA.chars().position(|h| h == c) else {
+            result.push(c);
+            continue;
+        };
+        let full = FULL_WIDTH_KATAKANA.chars().nth(index).unwrap_or(c);
+        let combined = match chars.peek() {
+            Some(&HALF_WIDTH_VOICED_MARK) => voiced(full),
+            Some(&HALF_WIDTH_SEMI_VOICED_MARK) => semi_voiced(full),
+            _ => None,
+        };
+        match combined {
+            Some(combined) => {
+                chars.next();
+                result.push(combined);
+            }
+            None => result.push(full),
+        }
+    }
+    result
+}
+
+/// 全角カタカナを半角にします。濁音と半濁音は 2 文字に分けます。
+pub fn to_half_width_katakana(text: &str) -> String {
+    let to_half = |c: char| {
+        FULL_WIDTH_KATAKANA
+            .chars()
+            .position(|f| f == c)
+            .and_then(|index| HALF_WIDTH_KATAKANA.chars().nth(index))
+    };
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        if let Some(half) = to_half(c) {
+            result.push(half);
+        } else if let Some(base) = FULL_WIDTH_KATAKANA.chars().find(|&f| voiced(f) == Some(c)) {
+            result.extend(to_half(base));
+            result.push(HALF_WIDTH_VOICED_MARK);
+        } else if let Some(base) = FULL_WIDTH_KATAKANA
+            .chars()
+            .find(|&f| semi_voiced(f) == Some(c))
+        {
+            result.extend(to_half(base));
+            result.push(HALF_WIDTH_SEMI_VOICED_MARK);
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// ひらがなとカタカナのコードポイントの差
+const KATAKANA_OFFSET: u32 = 0x60;
+
+/// ひらがなをカタカナにします。
+pub fn to_katakana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => {
+                char::from_u32(c as u32 + KATAKANA_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+/// カタカナをひらがなにします。対応するひらがなのない文字 (ヷなど) はそのまま残します。
+pub fn to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' | 'ヽ' | 'ヾ' => {
+                char::from_u32(c as u32 - KATAKANA_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trim_trailing_spaces_and_tabs() {
+        assert_eq!(
+            trim_trailing_whitespace("a  \n\tb\t\nc", false),
+            "a\n\tb\nc"
+        );
+        // CRLF の改行は残す
+        assert_eq!(trim_trailing_whitespace("a \r\nb", false), "a\r\nb");
+    }
+
+    #[test]
+    fn full_width_space_is_optional() {
+        // Given: 行末に全角空白がある行を
+        let text = "あ\u{3000} \nい";
+        // When: 全角空白を対象にしなければ
+        // Then: 半角空白だけを削除する
+        assert_eq!(trim_trailing_whitespace(text, false), "あ\u{3000}\nい");
+        // When: 対象にすれば
+        // Then: 全角空白も削除する
+        assert_eq!(trim_trailing_whitespace(text, true), "あ\nい");
+    }
+
+    #[test]
+    fn final_newline() {
+        assert_eq!(set_final_newline("a", FinalNewline::Ensure), "a\n");
+        assert_eq!(set_final_newline("a\n", FinalNewline::Ensure), "a\n");
+        assert_eq!(set_final_newline("", FinalNewline::Ensure), "");
+        assert_eq!(set_final_newline("a\n\r\n", FinalNewline::Remove), "a\n");
+        assert_eq!(set_final_newline("a\n", FinalNewline::Remove), "a");
+        assert_eq!(set_final_newline("a\n\n", FinalNewline::Keep), "a\n\n");
+    }
+
+    #[test]
+    fn final_newline_follows_crlf() {
+        // Given: CR LF の文書で
+        let text = "a\r\nb";
+        // When/Then: 追加する改行も CR LF にし、削除するときは CR LF をまとめて消す
+        assert_eq!(set_final_newline(text, FinalNewline::Ensure), "a\r\nb\r\n");
+        assert_eq!(
+            set_final_newline("a\r\n\r\n", FinalNewline::Remove),
+            "a\r\n"
+        );
+    }
+
+    #[test]
+    fn final_newline_setting_round_trip() {
+        for value in [
+            FinalNewline::Keep,
+            FinalNewline::Ensure,
+            FinalNewline::Remove,
+        ] {
+            assert_eq!(value.to_string().parse(), Ok(value));
+        }
+        assert_eq!("other".parse::<FinalNewline>(), Err(()));
+    }
+
+    #[test]
+    fn cleanup_applies_all_rules() {
+        let cleanup = Cleanup {
+            trim_trailing_whitespace: true,
+            trim_full_width_space: true,
+            final_newline: FinalNewline::Ensure,
+        };
+        assert_eq!(cleanup.apply("a \nb\u{3000}"), "a\nb\n");
+        assert_eq!(Cleanup::default().apply("a \n"), "a \n");
+    }
+
+    #[test]
+    fn caret_keeps_line_and_column() {
+        // Given: 2 行目の「い」の後ろにキャレットがあるとき
+        let old = "あ  \nいう  \n";
+        let caret = "あ  \nい".len();
+        // When: 行末の空白を削除しても
+        let new = trim_trailing_whitespace(old, false);
+        // Then: 同じ行と桁に置く
+        assert_eq!(map_caret(old, caret, &new), "あ\nい".len());
+        // 行末の空白の中にあったら、行末に置く
+        assert_eq!(map_caret(old, "あ  \nいう ".len(), &new), "あ\nいう".len());
+    }
+
+    #[test]
+    fn caret_on_removed_line_moves_to_last_line() {
+        let old = "a\n\n\n";
+        let new = set_final_newline(old, FinalNewline::Remove);
+        assert_eq!(map_caret(old, old.len(), &new), "a\n\n".len());
+    }
+
+    #[test]
+    fn line_range_covers_whole_lines() {
+        let text = "ab\ncd\nef";
+        // 選択していなければキャレットのある行
+        assert_eq!(line_range(text, 4..4), 3..5);
+        // 行の途中から途中まで
+        assert_eq!(line_range(text, 1..4), 0..5);
+        // 行頭で終わる選択は、その行を含めない
+        assert_eq!(line_range(text, 0..6), 0..5);
+        assert_eq!(line_range(text, 7..8), 6..8);
+    }
+
+    #[test]
+    fn tabs_become_spaces_up_to_tab_stop() {
+        // Given: 空白とタブが混ざったインデントを
+        let text = "\tA\n  \tB\t\n\r\nC\tD";
+        // When: 幅 4 で空白に変換すると
+        // Then: 次のタブ位置まで埋め、行頭以外のタブはそのまま残す
+        assert_eq!(tabs_to_spaces(text, 4), "    A\n    B\t\n\r\nC\tD");
+    }
+
+    #[test]
+    fn spaces_become_tabs_with_remainder() {
+        assert_eq!(
+            spaces_to_tabs("    A\n      B\n  C\r\n\t  D", 4),
+            "\tA\n\t  B\n  C\r\n\t  D"
+        );
+        // 空白に戻すと元に戻る
+        assert_eq!(
+            tabs_to_spaces(&spaces_to_tabs("        x", 4), 4),
+            "        x"
+        );
+    }
+
+    #[test]
+    fn detect_indentation_style() {
+        assert_eq!(detect_indentation("a\nb\n"), Indentation::Unknown);
+        assert_eq!(detect_indentation("if x {\n\ty\n}\n"), Indentation::Tabs);
+        assert_eq!(
+            detect_indentation("a:\n  b:\n    c\n"),
+            Indentation::Spaces(2)
+        );
+        assert_eq!(
+            detect_indentation("fn f() {\n    x\n        y\n}\n"),
+            Indentation::Spaces(4)
+        );
+        // 空白 1 つの位置合わせは数えない
+        assert_eq!(
+            detect_indentation("/*\n * 説明\n */\n\tx\n"),
+            Indentation::Tabs
+        );
+        assert_eq!(detect_indentation("\ta\n    b\n"), Indentation::Mixed);
+    }
+
+    #[test]
+    fn title_case() {
+        assert_eq!(
+            to_title_case("hello WORLD, don't panic-now"),
+            "Hello World, Don't Panic-Now"
+        );
+        assert_eq!(to_title_case("ＡＢＣ ｄｅｆ"), "Ａｂｃ Ｄｅｆ");
+    }
+
+    #[test]
+    fn alphanumeric_width() {
+        // Given: 英数字と記号と日本語が混ざった文字列を
+        let text = "ABC xyz 123-あ";
+        // When: 全角にすると
+        let full = to_full_width_alphanumeric(text);
+        // Then: 英数字だけが全角になり、半角に戻すと元に戻る
+        assert_eq!(full, "ＡＢＣ ｘｙｚ １２３-あ");
+        assert_eq!(to_half_width_alphanumeric(&full), text);
+    }
+
+    #[test]
+    fn half_width_katakana_to_full_width() {
+        assert_eq!(
+            to_full_width_katakana("ｶﾞｯｺｳ ﾊﾟﾝ ｳﾞｧｲｵﾘﾝ ｱﾞ｢ﾃｽﾄ｣"),
+            "ガッコウ パン ヴァイオリン ア゛｢テスト｣"
+        );
+    }
+
+    #[test]
+    fn full_width_katakana_to_half_width() {
+        // 濁音と半濁音は 2 文字に分け、ひらがなや記号は変えない
+        assert_eq!(
+            to_half_width_katakana("ガッコウ・パン・ヴ、ひらがな。「ー゛゜」"),
+            "ｶﾞｯｺｳ・ﾊﾟﾝ・ｳﾞ、ひらがな。「ｰﾞﾟ」"
+        );
+        assert_eq!(
+            to_full_width_katakana(&to_half_width_katakana("ブラウザー")),
+            "ブラウザー"
+        );
+    }
+
+    #[test]
+    fn hiragana_and_katakana() {
+        assert_eq!(to_katakana("ひらがなとゝ、ヴ"), "ヒラガナトヽ、ヴ");
+        assert_eq!(to_hiragana("カタカナとヴ・ヷー"), "かたかなとゔ・ヷー");
+    }
+}
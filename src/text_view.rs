@@ -0,0 +1,542 @@
+// 編集に使うコントロールの違いを隠し、wx::TextCtrl と
+// wx::StyledTextCtrl (Scintilla) を同じように扱えるようにします。
+
+use std::cell::Cell;
+use std::fmt;
+use std::os::raw::{c_int, c_long, c_void};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use wx::methods::*;
+
+use crate::encoding::LineEnding;
+use crate::line_numbers::{self, LineNumberGutter};
+use crate::position::Unit;
+use crate::syntax::Language;
+
+// 検索で一致したところの背景色
+const HIGHLIGHT_COLOUR: &str = "#FFFF80";
+
+/// 設定で選べる編集コントロールの種類
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Backend {
+    #[default]
+    TextCtrl,
+    StyledTextCtrl,
+}
+impl FromStr for Backend {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "textctrl" => Ok(Backend::TextCtrl),
+            "scintilla" => Ok(Backend::StyledTextCtrl),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::TextCtrl => "textctrl",
+            Backend::StyledTextCtrl => "scintilla",
+        })
+    }
+}
+
+/// 編集コントロールに求める操作です。位置は `unit()` の単位で数えます。
+pub trait TextView {
+    /// 位置を数える単位
+    fn unit(&self) -> Unit;
+
+    fn add_to_sizer(&self, sizer: &wx::BoxSizer, proportion: c_int, flag: c_int);
+    fn set_focus(&self);
+    /// ユーザーの入力や `replace()` でテキストが変わったときに呼びます。
+    fn bind_text_changed(&self, handler: Box<dyn Fn()>);
+    fn bind_mouse_wheel(&self, handler: Box<dyn Fn(&wx::MouseEvent)>);
+    fn bind_key_down(&self, handler: Box<dyn Fn(&wx::KeyEvent)>);
+
+    fn text(&self) -> String;
+    /// 読み込んだテキストを表示します。変更の通知は送りません。
+    fn set_text(&self, text: &str);
+    /// `from..to` を `text` に置き換えます。1 回で元に戻せます。
+    fn replace(&self, from: c_long, to: c_long, text: &str);
+    fn last_position(&self) -> c_long;
+
+    fn selection(&self) -> (c_long, c_long);
+    fn set_selection(&self, from: c_long, to: c_long);
+    fn insertion_point(&self) -> c_long;
+    fn set_insertion_point(&self, pos: c_long);
+    fn show_position(&self, pos: c_long);
+    /// 一番上に表示している行
+    fn first_visible_line(&self) -> i32;
+    fn scroll_to_line(&self, line: i32);
+
+    fn is_modified(&self) -> bool;
+    fn set_modified(&self, modified: bool);
+    fn can_undo(&self) -> bool;
+    fn set_editable(&self, editable: bool);
+    /// 元に戻す、切り取り、コピーなどの標準のメニューコマンドを処理します。
+    fn process_standard_event(&self, event: &wx::CommandEvent);
+
+    fn font(&self) -> wx::Font;
+    fn set_font(&self, font: &wx::Font);
+    /// 空白 `tab_width` 文字ごとにタブ位置を設定します。
+    fn set_tab_width(&self, tab_width: usize);
+    fn word_wrap(&self) -> bool;
+    fn set_word_wrap(&self, word_wrap: bool);
+    fn line_numbers(&self) -> bool;
+    /// テキストの左に行番号を表示します。
+    fn set_line_numbers(&self, visible: bool);
+    /// 言語に合わせて色分けできるなら真を返します。
+    fn supports_syntax(&self) -> bool;
+    /// `language` に合わせて色分けします。`None` なら色分けしません。
+    fn set_language(&self, language: Option<&'static Language>);
+    /// 検索で一致したところに色を付けます。
+    fn highlight(&self, from: c_long, to: c_long);
+    /// `from..to` に付けた色を消します。
+    fn clear_highlight(&self, from: c_long, to: c_long);
+    /// 色を付けると、元に戻す操作の履歴に入るなら真を返します。
+    fn highlight_is_undoable(&self) -> bool;
+}
+
+pub fn create<W: WindowMethods>(parent: &W, backend: Backend) -> Rc<dyn TextView> {
+    match backend {
+        Backend::TextCtrl => Rc::new(TextCtrlView::new(parent)),
+        Backend::StyledTextCtrl => Rc::new(StyledTextView::new(parent)),
+    }
+}
+
+/// wx::TextCtrl を使う、OS 標準の編集コントロールです。
+struct TextCtrlView {
+    ctrl: wx::TextCtrl,
+    gutter: Rc<LineNumberGutter>,
+}
+impl TextCtrlView {
+    fn new<W: WindowMethods>(parent: &W) -> Self {
+        let ctrl = wx::TextCtrl::builder(Some(parent))
+            // 検索結果に色を付けるため、書式を扱えるようにします。
+            // Tab キーはフォーカスの移動ではなく、インデントに使います。
+            .style((wx::TE_MULTILINE | wx::TE_RICH2 | wx::TE_PROCESS_TAB).into())
+            .build();
+        let gutter = LineNumberGutter::new(parent, &ctrl);
+        Self { ctrl, gutter }
+    }
+
+    /// 書式を変えても、変更ありにならないようにします。
+    fn set_style(&self, from: c_long, to: c_long, attr: &wx::TextAttr) {
+        let modified = self.ctrl.is_modified();
+        self.ctrl.set_style(from, to, attr);
+        if !modified {
+            self.ctrl.discard_edits();
+        }
+    }
+}
+impl TextView for TextCtrlView {
+    fn unit(&self) -> Unit {
+        crate::position::NATIVE
+    }
+
+    fn add_to_sizer(&self, sizer: &wx::BoxSizer, proportion: c_int, flag: c_int) {
+        self.gutter.add_to_sizer(sizer, proportion, flag);
+    }
+    fn set_focus(&self) {
+        self.ctrl.set_focus();
+    }
+    fn bind_text_changed(&self, handler: Box<dyn Fn()>) {
+        self.ctrl
+            .bind(wx::RustEvent::Text, move |_: &wx::CommandEvent| handler());
+    }
+    fn bind_mouse_wheel(&self, handler: Box<dyn Fn(&wx::MouseEvent)>) {
+        self.ctrl
+            .bind(wx::RustEvent::MouseWheel, move |event: &wx::MouseEvent| {
+                handler(event)
+            });
+    }
+    fn bind_key_down(&self, handler: Box<dyn Fn(&wx::KeyEvent)>) {
+        self.ctrl
+            .bind(wx::RustEvent::KeyDown, move |event: &wx::KeyEvent| {
+                handler(event)
+            });
+    }
+
+    fn text(&self) -> String {
+        self.ctrl.get_value()
+    }
+    fn set_text(&self, text: &str) {
+        self.ctrl.change_value(text);
+        self.gutter.text_changed();
+    }
+    fn replace(&self, from: c_long, to: c_long, text: &str) {
+        self.ctrl.replace(from, to, text);
+    }
+    fn last_position(&self) -> c_long {
+        self.ctrl.get_last_position()
+    }
+
+    fn selection(&self) -> (c_long, c_long) {
+        let mut from: c_long = 0;
+        let mut to: c_long = 0;
+        self.ctrl.get_selection_long(
+            &mut from as *mut c_long as *mut c_void,
+            &mut to as *mut c_long as *mut c_void,
+        );
+        (from, to)
+    }
+    fn set_selection(&self, from: c_long, to: c_long) {
+        self.ctrl.set_selection(from, to);
+    }
+    fn insertion_point(&self) -> c_long {
+        self.ctrl.get_insertion_point()
+    }
+    fn set_insertion_point(&self, pos: c_long) {
+        self.ctrl.set_insertion_point(pos);
+    }
+    fn show_position(&self, pos: c_long) {
+        self.ctrl.show_position(pos);
+    }
+    fn first_visible_line(&self) -> i32 {
+        self.ctrl.get_scroll_pos(wx::VERTICAL)
+    }
+    fn scroll_to_line(&self, line: i32) {
+        self.ctrl.show_position(0);
+        self.ctrl.scroll_lines(line);
+    }
+
+    fn is_modified(&self) -> bool {
+        self.ctrl.is_modified()
+    }
+    fn set_modified(&self, modified: bool) {
+        self.ctrl.set_modified(modified);
+    }
+    fn can_undo(&self) -> bool {
+        self.ctrl.can_undo()
+    }
+    fn set_editable(&self, editable: bool) {
+        self.ctrl.set_editable(editable);
+    }
+    fn process_standard_event(&self, event: &wx::CommandEvent) {
+        if event.get_id() == wx::ID_SELECTALL {
+            // GTK+ では wx::TextCtrl が wx::ID_SELECTALL を処理しないため、
+            // 自前で呼び出します。
+            self.ctrl.select_all();
+            return;
+        }
+        self.ctrl.process_event(event);
+    }
+
+    fn font(&self) -> wx::Font {
+        self.ctrl.get_font()
+    }
+    fn set_font(&self, font: &wx::Font) {
+        self.ctrl.set_font(font);
+        self.gutter.font_changed();
+    }
+    fn set_tab_width(&self, tab_width: usize) {
+        // タブ位置は 0.1 mm 単位で指定します。
+        let dpi = self.ctrl.get_dpi().get_width().max(1);
+        let step = self.ctrl.get_char_width() * tab_width as i32 * 254 / dpi;
+        let tabs = wx::ArrayInt::new();
+        for i in 1..=100 {
+            tabs.add(step * i);
+        }
+        let attr = wx::TextAttr::new();
+        attr.set_tabs(&tabs);
+        self.ctrl.set_default_style(&attr);
+        self.set_style(0, self.ctrl.get_last_position(), &attr);
+    }
+    fn word_wrap(&self) -> bool {
+        self.ctrl.get_window_style_flag() & wx::TE_DONTWRAP == 0
+    }
+    fn set_word_wrap(&self, word_wrap: bool) {
+        let style = self.ctrl.get_window_style_flag();
+        let style = if word_wrap {
+            style & !wx::TE_DONTWRAP
+        } else {
+            style | wx::TE_DONTWRAP
+        };
+        // wxMSW ではコントロールを作り直して反映します。
+        self.ctrl.set_window_style_flag(style);
+        // 折り返すと行の位置が変わるので、行番号も描き直します。
+        self.gutter.refresh();
+    }
+    fn line_numbers(&self) -> bool {
+        self.gutter.is_shown()
+    }
+    fn set_line_numbers(&self, visible: bool) {
+        self.gutter.show(visible);
+    }
+    fn supports_syntax(&self) -> bool {
+        false
+    }
+    fn set_language(&self, _language: Option<&'static Language>) {}
+    fn highlight(&self, from: c_long, to: c_long) {
+        let attr = wx::TextAttr::new();
+        attr.set_background_colour(&wx::Colour::new_with_str(HIGHLIGHT_COLOUR));
+        self.set_style(from, to, &attr);
+    }
+    fn clear_highlight(&self, from: c_long, to: c_long) {
+        let attr = wx::TextAttr::new();
+        attr.set_background_colour(&self.ctrl.get_background_colour());
+        self.set_style(from, to.min(self.ctrl.get_last_position()), &attr);
+    }
+    fn highlight_is_undoable(&self) -> bool {
+        // Windows の RichEdit は、書式の変更も元に戻す操作として記録します。
+        cfg!(windows)
+    }
+}
+
+// 検索結果に使う Scintilla のインジケーター
+const HIGHLIGHT_INDICATOR: c_int = 8;
+
+/// wx::StyledTextCtrl (Scintilla) を使う編集コントロールです。
+/// 位置は UTF-8 のバイト位置で数えます。
+struct StyledTextView {
+    ctrl: wx::StyledTextCtrl,
+    // `set_text()` の間は変更を通知しません。
+    loading: Rc<Cell<bool>>,
+    line_numbers: Rc<Cell<bool>>,
+    language: Cell<Option<&'static Language>>,
+}
+impl StyledTextView {
+    fn new<W: WindowMethods>(parent: &W) -> Self {
+        let ctrl = wx::StyledTextCtrl::builder(Some(parent)).build();
+        // 余白 0 に行番号を表示し、それ以外の余白は使いません。
+        // 行番号の余白をクリックすると、Scintilla がその行を選択します。
+        ctrl.set_margin_type(LINE_NUMBER_MARGIN, wx::STC_MARGIN_NUMBER);
+        for margin in 0..3 {
+            ctrl.set_margin_width(margin, 0);
+        }
+        ctrl.indicator_set_style(HIGHLIGHT_INDICATOR, wx::STC_INDIC_FULLBOX);
+        ctrl.indicator_set_foreground(
+            HIGHLIGHT_INDICATOR,
+            &wx::Colour::new_with_str(HIGHLIGHT_COLOUR),
+        );
+        ctrl.indicator_set_alpha(HIGHLIGHT_INDICATOR, 255);
+        ctrl.indicator_set_under(HIGHLIGHT_INDICATOR, true);
+        let line_numbers = Rc::new(Cell::new(false));
+        // 行数の桁が変わったら、余白の幅を合わせます。
+        let ctrl_copy = ctrl.clone();
+        let line_numbers_copy = line_numbers.clone();
+        ctrl.bind(
+            wx::RustEvent::StcChange,
+            move |event: &wx::StyledTextEvent| {
+                if line_numbers_copy.get() {
+                    update_line_number_margin(&ctrl_copy);
+                }
+                event.skip(true);
+            },
+        );
+        Self {
+            ctrl,
+            loading: Rc::new(Cell::new(false)),
+            line_numbers,
+            language: Cell::new(None),
+        }
+    }
+
+    fn apply_language(&self) {
+        // 前の言語の色を消します。
+        self.ctrl.style_clear_all();
+        match self.language.get() {
+            Some(language) => {
+                self.ctrl.set_lexer(language.lexer);
+                for (i, keywords) in language.keywords.iter().enumerate() {
+                    self.ctrl.set_key_words(i as c_int, keywords);
+                }
+                for &(style, token) in language.styles {
+                    self.ctrl
+                        .style_set_foreground(style, &wx::Colour::new_with_str(token.colour()));
+                    self.ctrl.style_set_bold(style, token.is_bold());
+                }
+            }
+            None => self.ctrl.set_lexer(wx::STC_LEX_NULL),
+        }
+        self.ctrl.colourise(0, -1);
+    }
+}
+
+// 行番号を表示する Scintilla の余白
+const LINE_NUMBER_MARGIN: c_int = 0;
+
+fn update_line_number_margin(ctrl: &wx::StyledTextCtrl) {
+    let digits = line_numbers::digits(ctrl.get_line_count() as usize);
+    // 先頭の "_" は左右の余白の分です。
+    let width = ctrl.text_width(
+        wx::STC_STYLE_LINENUMBER,
+        &format!("_{}", "9".repeat(digits)),
+    );
+    if width != ctrl.get_margin_width(LINE_NUMBER_MARGIN) {
+        ctrl.set_margin_width(LINE_NUMBER_MARGIN, width);
+    }
+}
+impl TextView for StyledTextView {
+    fn unit(&self) -> Unit {
+        Unit::Byte
+    }
+
+    fn add_to_sizer(&self, sizer: &wx::BoxSizer, proportion: c_int, flag: c_int) {
+        sizer.add_window_int(Some(&self.ctrl), proportion, flag, 0, wx::Object::none());
+    }
+    fn set_focus(&self) {
+        self.ctrl.set_focus();
+    }
+    fn bind_text_changed(&self, handler: Box<dyn Fn()>) {
+        let loading = self.loading.clone();
+        self.ctrl
+            .bind(wx::RustEvent::StcChange, move |_: &wx::StyledTextEvent| {
+                if !loading.get() {
+                    handler();
+                }
+            });
+    }
+    fn bind_mouse_wheel(&self, handler: Box<dyn Fn(&wx::MouseEvent)>) {
+        self.ctrl
+            .bind(wx::RustEvent::MouseWheel, move |event: &wx::MouseEvent| {
+                handler(event)
+            });
+    }
+    fn bind_key_down(&self, handler: Box<dyn Fn(&wx::KeyEvent)>) {
+        self.ctrl
+            .bind(wx::RustEvent::KeyDown, move |event: &wx::KeyEvent| {
+                handler(event)
+            });
+    }
+
+    fn text(&self) -> String {
+        self.ctrl.get_text()
+    }
+    fn set_text(&self, text: &str) {
+        self.loading.set(true);
+        // Enter で入力する改行を、読み込んだテキストの改行コードに合わせます。
+        self.ctrl.set_eol_mode(match LineEnding::detect(text) {
+            LineEnding::Lf => wx::STC_EOL_LF,
+            LineEnding::CrLf => wx::STC_EOL_CRLF,
+        });
+        self.ctrl.set_text(text);
+        // 読み込む前の内容には戻せないようにします。
+        self.ctrl.empty_undo_buffer();
+        self.loading.set(false);
+    }
+    fn replace(&self, from: c_long, to: c_long, text: &str) {
+        self.ctrl.begin_undo_action();
+        self.ctrl.set_target_start(from as c_int);
+        self.ctrl.set_target_end(to as c_int);
+        self.ctrl.replace_target(text);
+        self.ctrl.end_undo_action();
+    }
+    fn last_position(&self) -> c_long {
+        self.ctrl.get_length() as c_long
+    }
+
+    fn selection(&self) -> (c_long, c_long) {
+        (
+            self.ctrl.get_selection_start() as c_long,
+            self.ctrl.get_selection_end() as c_long,
+        )
+    }
+    fn set_selection(&self, from: c_long, to: c_long) {
+        self.ctrl.set_selection(from, to);
+    }
+    fn insertion_point(&self) -> c_long {
+        self.ctrl.get_current_pos() as c_long
+    }
+    fn set_insertion_point(&self, pos: c_long) {
+        self.ctrl.goto_pos(pos as c_int);
+    }
+    fn show_position(&self, pos: c_long) {
+        self.ctrl.scroll_range(pos as c_int, pos as c_int);
+    }
+    fn first_visible_line(&self) -> i32 {
+        self.ctrl.get_first_visible_line()
+    }
+    fn scroll_to_line(&self, line: i32) {
+        self.ctrl.set_first_visible_line(line);
+    }
+
+    fn is_modified(&self) -> bool {
+        self.ctrl.get_modify()
+    }
+    fn set_modified(&self, modified: bool) {
+        if modified {
+            self.ctrl.mark_dirty();
+        } else {
+            self.ctrl.set_save_point();
+        }
+    }
+    fn can_undo(&self) -> bool {
+        self.ctrl.can_undo()
+    }
+    fn set_editable(&self, editable: bool) {
+        self.ctrl.set_read_only(!editable);
+    }
+    fn process_standard_event(&self, event: &wx::CommandEvent) {
+        // wx::StyledTextCtrl は標準のメニューコマンドを処理しないため、自前で呼び出します。
+        match event.get_id() {
+            wx::ID_UNDO => self.ctrl.undo(),
+            wx::ID_REDO => self.ctrl.redo(),
+            wx::ID_CUT => self.ctrl.cut(),
+            wx::ID_COPY => self.ctrl.copy(),
+            wx::ID_PASTE => self.ctrl.paste(),
+            wx::ID_SELECTALL => self.ctrl.select_all(),
+            _ => (),
+        }
+    }
+
+    fn font(&self) -> wx::Font {
+        self.ctrl.style_get_font(wx::STC_STYLE_DEFAULT)
+    }
+    fn set_font(&self, font: &wx::Font) {
+        self.ctrl.style_set_font(wx::STC_STYLE_DEFAULT, font);
+        // 既定のスタイルを他のスタイルにも反映し、色分けをやり直します。
+        self.apply_language();
+        if self.line_numbers.get() {
+            update_line_number_margin(&self.ctrl);
+        }
+    }
+    fn set_tab_width(&self, tab_width: usize) {
+        self.ctrl.set_tab_width(tab_width as c_int);
+    }
+    fn word_wrap(&self) -> bool {
+        self.ctrl.get_wrap_mode() != wx::STC_WRAP_NONE
+    }
+    fn set_word_wrap(&self, word_wrap: bool) {
+        // 日本語は空白で区切らないため、文字単位で折り返します。
+        self.ctrl.set_wrap_mode(if word_wrap {
+            wx::STC_WRAP_CHAR
+        } else {
+            wx::STC_WRAP_NONE
+        });
+    }
+    fn line_numbers(&self) -> bool {
+        self.line_numbers.get()
+    }
+    fn set_line_numbers(&self, visible: bool) {
+        self.line_numbers.set(visible);
+        if visible {
+            update_line_number_margin(&self.ctrl);
+        } else {
+            self.ctrl.set_margin_width(LINE_NUMBER_MARGIN, 0);
+        }
+    }
+    fn supports_syntax(&self) -> bool {
+        true
+    }
+    fn set_language(&self, language: Option<&'static Language>) {
+        self.language.set(language);
+        self.apply_language();
+    }
+    fn highlight(&self, from: c_long, to: c_long) {
+        self.ctrl.set_indicator_current(HIGHLIGHT_INDICATOR);
+        self.ctrl
+            .indicator_fill_range(from as c_int, (to - from) as c_int);
+    }
+    fn clear_highlight(&self, from: c_long, to: c_long) {
+        let to = (to as c_int).min(self.ctrl.get_length());
+        self.ctrl.set_indicator_current(HIGHLIGHT_INDICATOR);
+        self.ctrl
+            .indicator_clear_range(from as c_int, to - (from as c_int).min(to));
+    }
+    fn highlight_is_undoable(&self) -> bool {
+        false
+    }
+}
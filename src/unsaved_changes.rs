@@ -3,6 +3,23 @@ use crate::editor_ctrl::Document;
 pub trait UI {
     fn confirm_save<CB: FnOnce(Option<bool>)>(&self, on_complete: CB);
     fn get_path_to_save<CB: FnMut(Option<String>)>(&self, on_complete: CB);
+    fn confirm_lossy_save(&self) -> bool;
+}
+
+/// 保存すると内容が失われる場合は確認してから保存します。
+pub fn save_to<D: Document, U: UI>(doc: &D, ui: &U, path: &str) -> bool {
+    if doc.is_lossy() && !ui.confirm_lossy_save() {
+        return false;
+    }
+    doc.save_to(path)
+}
+
+/// 内容を別のファイルに書き出します。ドキュメントのパスと変更フラグは変わりません。
+pub fn save_copy_to<D: Document, U: UI>(doc: &D, ui: &U, path: &str) -> bool {
+    if doc.is_lossy() && !ui.confirm_lossy_save() {
+        return false;
+    }
+    doc.save_copy_to(path)
 }
 
 // TODO: future 的なインターフェイス
@@ -16,13 +33,13 @@ pub fn save<D: Document, U: UI, CB: Fn(&D, bool)>(doc: &D, ui: &U, on_complete:
             if do_save {
                 // 確認ダイアログで「保存する」
                 if let Some(path) = doc.path() {
-                    let saved = doc.save_to(&path);
+                    let saved = save_to(doc, ui, &path);
                     on_complete(doc, saved);
                 } else {
                     ui.get_path_to_save(|path| {
                         let saved = if let Some(path) = path {
                             // TODO: エラーを返す
-                            doc.save_to(&path)
+                            save_to(doc, ui, &path)
                         } else {
                             false
                         };
@@ -54,6 +71,7 @@ mod test {
     struct MockDoc {
         path: Option<String>,
         modified: RefCell<bool>,
+        lossy: bool,
         save_wont_be_called: bool,
         save_will_fail: bool,
     }
@@ -62,6 +80,7 @@ mod test {
             Self {
                 path: None,
                 modified: RefCell::new(true),
+                lossy: false,
                 save_wont_be_called: false,
                 save_will_fail: false,
             }
@@ -83,6 +102,10 @@ mod test {
             *self.modified.borrow()
         }
 
+        fn is_lossy(&self) -> bool {
+            self.lossy
+        }
+
         fn load_from(&self, _file_path: &str) {
             todo!()
         }
@@ -95,6 +118,11 @@ mod test {
             *self.modified.borrow_mut() = false;
             true
         }
+
+        fn save_copy_to(&self, _file_path: &str) -> bool {
+            assert!(!self.save_wont_be_called);
+            !self.save_will_fail
+        }
     }
 
     struct MockSaveUI {
@@ -103,6 +131,8 @@ mod test {
         confirm_result: Option<bool>,
         save_dlg_wont_be_called: bool,
         save_dlg_will_be_cancelled: bool,
+        lossy_confirm_wont_be_called: bool,
+        lossy_confirm_result: bool,
     }
     impl MockSaveUI {
         fn new() -> Self {
@@ -111,6 +141,8 @@ mod test {
                 confirm_result: Some(true),
                 save_dlg_wont_be_called: false,
                 save_dlg_will_be_cancelled: false,
+                lossy_confirm_wont_be_called: false,
+                lossy_confirm_result: true,
             }
         }
     }
@@ -127,6 +159,10 @@ mod test {
             }
             on_complete(Some("path/to/save".to_owned()))
         }
+        fn confirm_lossy_save(&self) -> bool {
+            assert!(!self.lossy_confirm_wont_be_called);
+            self.lossy_confirm_result
+        }
     }
 
     #[test]
@@ -243,4 +279,61 @@ mod test {
             assert!(!saved);
         });
     }
+
+    #[test]
+    fn lossless_doc_is_saved_without_lossy_confirm() {
+        // Given: 保存しても内容が失われないドキュメント
+        let mut doc = MockDoc::new();
+        doc.path = Some("dummy".to_owned());
+
+        let mut ui = MockSaveUI::new();
+        // Then: 内容が失われる確認は呼ばれない
+        ui.lossy_confirm_wont_be_called = true;
+        save(&mut doc, &ui, |_doc, saved| {
+            assert!(saved);
+        });
+    }
+
+    #[test]
+    fn lossy_doc_keeps_modified_if_lossy_confirm_declined() {
+        // Given: 保存すると内容が失われるドキュメントで
+        let mut doc = MockDoc::new();
+        doc.path = Some("dummy".to_owned());
+        doc.lossy = true;
+
+        let mut ui = MockSaveUI::new();
+        // When: 内容が失われる確認で「いいえ」を選んだら
+        ui.lossy_confirm_result = false;
+        // Then: 保存は行われず
+        doc.save_wont_be_called = true;
+        save(&mut doc, &ui, |_doc, saved| {
+            // Then: 変更フラグは立ったまま
+            assert!(!saved);
+        });
+    }
+
+    #[test]
+    fn lossy_doc_is_saved_if_lossy_confirm_accepted() {
+        // Given: 保存すると内容が失われるドキュメントで
+        let mut doc = MockDoc::new();
+        doc.lossy = true;
+
+        let ui = MockSaveUI::new();
+        // When: 内容が失われる確認で「はい」を選んだら
+        save(&mut doc, &ui, |_doc, saved| {
+            // Then: 保存される
+            assert!(saved);
+        });
+    }
+
+    #[test]
+    fn lossy_doc_is_not_copied_if_lossy_confirm_declined() {
+        let mut doc = MockDoc::new();
+        doc.lossy = true;
+        doc.save_wont_be_called = true;
+
+        let mut ui = MockSaveUI::new();
+        ui.lossy_confirm_result = false;
+        assert!(!save_copy_to(&doc, &ui, "copy"));
+    }
 }